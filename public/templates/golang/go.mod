@@ -1,3 +1,3 @@
-module github.com/yourusername/yourprojectname
+module {{project_name}}
 
 go 1.21.1
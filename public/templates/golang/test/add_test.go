@@ -2,7 +2,7 @@ package test
 
 import (
     "testing"
-    "github.com/yourusername/yourprojectname/pkg/mathops"
+    "{{project_name}}/pkg/mathops"
 )
 
 func TestAdd(t *testing.T) {
@@ -1,42 +1,104 @@
 use crate::directory::Directory;
+use crate::template::Manifest;
 use crate::utils::{
-    dir::{self, compare_dir},
-    replace::replace_command,
+    cache::Fingerprint,
+    dir::{self, compare_dir, render_dir},
+    process::{catch_signals, clear_restart, pause, restart, take_signal, Process, Program, POLL},
+    replace::{has_variable, replace_argv, replace_command, replace_text},
+    watch::Changes,
 };
 use anyhow::{anyhow, Result};
-use colored::Colorize;
-use dialoguer::{theme::ColorfulTheme, Select};
-use dirs;
-use fs_extra::dir::copy;
+use chrono::Local;
+use colored::{Color, Colorize};
+use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Select};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs,
-    io::{BufWriter, Write},
-    time::Instant,
+    fmt, fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
     {
         collections::{BTreeMap, HashMap, HashSet},
         env,
-        path::PathBuf,
+        path::{Path, PathBuf},
     },
 };
-use subprocess;
+use subprocess::ExitStatus;
 
 type TemplateName = String;
 
-// Output type
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(rename_all = "lowercase")]
-pub enum Output {
-    Optional,
-    Required,
-    None,
-}
+// Project-local configuration file
+pub const LOCAL_CONFIG: &str = ".tau.json";
+
+// Colors of the task names in the output of a command graph
+const COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::Red,
+];
+
+// Template variables
+pub type Variables = BTreeMap<String, String>;
+
+// Environment variables of the tasks
+pub type Env = BTreeMap<String, String>;
+
+// Values of the command arguments, each value is quoted on its own
+pub type ArgValues<'a> = HashMap<&'a str, Vec<String>>;
 
 // Command argument
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Arg {
     pub name: String,
     pub description: Option<String>,
+    // Arguments are required unless they have a default value or are flags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    // Argument without value, replaced by true or false
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag: Option<bool>,
+    // Values allowed for the argument
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<String>>,
+    // Argument given by its position instead of --name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub positional: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short: Option<char>,
+    // Argument with several values, replaced by the values separated by spaces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiple: Option<bool>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ArgType>,
+    // Regular expression that the values must match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+// Type of the values of an argument
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgType {
+    #[default]
+    String,
+    Int,
+    // Paths are relative to the folder where tau was called
+    Path,
+    ExistingFile,
+    ExistingDir,
+    // One of the choices of the argument
+    Enum,
 }
 
 // Project commands
@@ -45,13 +107,121 @@ pub struct CommandProject {
     pub tasks: Vec<Task>,
     pub args: Option<Vec<Arg>>,
     pub description: Option<String>,
+    // Run the remaining tasks when one fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub continue_on_error: Option<bool>,
+    // Glob patterns of the files that run the command again in watch mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Env>,
+}
+
+// Output of a task
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+    // Printed while the task runs
+    #[default]
+    Stream,
+    // Printed when the task finishes
+    Buffer,
 }
 
 // Task to execute
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Task {
     pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub command: String,
+    // Program and arguments that run without a shell, instead of the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub argv: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Output>,
+    // Attached to the terminal, for prompts, REPLs and debuggers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactive: Option<bool>,
+    // Run even after a failure or a stop signal, for cleanups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub always: Option<bool>,
+    // Tasks of the same command or other commands that must finish before
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends_on: Option<Vec<String>>,
+    // Glob patterns of the files the task reads, it is skipped while they do not change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Vec<String>>,
+    // Glob patterns of the files the task creates, they must exist to skip it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Env>,
+    // Folder where the task runs, relative to the workspace
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    // Maximum duration of the task, like 30s or 5m
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<String>,
+    // Number of times the task runs again after it fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    // Time to wait before running the task again
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_delay: Option<String>,
+}
+
+// Task that did not finish successfully
+#[derive(Debug)]
+pub struct TaskError {
+    pub task: String,
+    pub code: i32,
+    pub reason: Reason,
+}
+
+// Why a task did not succeed
+#[derive(Debug, PartialEq)]
+pub enum Reason {
+    Failed,
+    Interrupted,
+    TimedOut(Duration),
+}
+
+// Task of a command graph
+struct Node<'a> {
+    name: String,
+    task: &'a Task,
+    // Environment variables of the command of the task
+    env: Option<&'a Env>,
+    // Positions of the tasks that must finish before
+    depends_on: Vec<usize>,
+}
+
+// State of a task of a command graph
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Waiting,
+    Running,
+    Done,
+    Failed,
+    Skipped,
+}
+
+// Task of a command graph that is running
+struct Job {
+    position: usize,
+    process: Process,
+    reader: Option<JoinHandle<String>>,
+    fingerprint: Option<Fingerprint>,
+    prefix: String,
+    interactive: bool,
+    start: Instant,
+}
+
+// Tasks executed at moments of the project life
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Hooks {
+    #[serde(default)]
+    pub post_create: Vec<Task>,
 }
 
 // Project options
@@ -60,16 +230,45 @@ pub struct ProjectConfig {
     pub optional_files: Vec<String>,
     pub commands: HashMap<String, CommandProject>,
     pub routes: HashSet<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+    // Environment variables of every task of the template
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Env>,
 }
 
 // Config
 #[derive(Serialize, Deserialize)]
 pub struct Config(pub BTreeMap<TemplateName, ProjectConfig>);
 
+// Project-local configuration
+#[derive(Serialize, Deserialize, Default)]
+pub struct LocalConfig {
+    #[serde(default)]
+    pub variables: Variables,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub commands: HashMap<String, CommandProject>,
+}
+
 // Target
 pub struct Details {
     pub workspace: PathBuf,
     pub src: PathBuf,
+    // Folder where tau was called
+    pub cwd: PathBuf,
+    pub variables: Variables,
+}
+
+// Options of a command run
+#[derive(Default)]
+pub struct RunOptions {
+    pub time: bool,
+    // Run the tasks even if they are up to date
+    pub force: bool,
+    // Tasks of a graph that run at the same time
+    pub jobs: Option<usize>,
+    // Loads the .env.<profile> file
+    pub profile: Option<String>,
 }
 
 // Program context
@@ -77,29 +276,385 @@ pub struct Context {
     pub details: Details,
     pub project_config: ProjectConfig,
     pub template_name: String,
+    // Commands of the template with the commands of the project
+    pub commands: HashMap<String, CommandProject>,
 }
 
 impl Details {
     // Get project details
-    pub fn from(workspace: &PathBuf) -> Details {
+    pub fn from(workspace: &Path, variables: Variables) -> Details {
         let src = workspace.join("src");
+        let mut variables = variables;
+
+        if let Some(name) = workspace.file_name().and_then(|s| s.to_str()) {
+            variables
+                .entry("project_name".to_string())
+                .or_insert(name.to_string());
+        }
 
         Details {
-            workspace: workspace.clone(),
+            workspace: workspace.to_path_buf(),
             src,
+            cwd: env::current_dir().unwrap_or(workspace.to_path_buf()),
+            variables,
+        }
+    }
+}
+
+impl LocalConfig {
+    // Get the configuration of a project, if it has one
+    pub fn from(workspace: &Path) -> Result<LocalConfig> {
+        let path = workspace.join(LOCAL_CONFIG);
+
+        if !path.exists() {
+            return Ok(LocalConfig::default());
+        }
+
+        let local_config = fs::read_to_string(&path)?;
+
+        match serde_json::from_str(&local_config) {
+            Ok(local_config) => Ok(local_config),
+            Err(e) => Err(anyhow!(
+                "Invalid project config ({}): {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    // Save the configuration in the project
+    pub fn save(&self, workspace: &Path) -> Result<()> {
+        let path = workspace.join(LOCAL_CONFIG);
+        fs::write(path, serde_json::to_string_pretty(&self)?)?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            Reason::Failed => write!(
+                f,
+                "The task {} failed with exit code {}",
+                self.task, self.code
+            ),
+            Reason::Interrupted => write!(f, "The task {} was interrupted", self.task),
+            Reason::TimedOut(timeout) => write!(
+                f,
+                "The task {} timed out after {}",
+                self.task,
+                humantime::format_duration(timeout)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+impl TaskError {
+    // Error of a finished task, if it did not succeed
+    fn from(task: &str, status: ExitStatus, process: &Process) -> Option<TaskError> {
+        if let Some(signal) = process.interrupted {
+            return Some(TaskError::interrupted(task, signal));
+        }
+
+        // Same exit code as the timeout command
+        if let Some(timeout) = process.timed_out {
+            return Some(TaskError {
+                task: task.to_string(),
+                code: 124,
+                reason: Reason::TimedOut(timeout),
+            });
+        }
+
+        if status.success() {
+            return None;
+        }
+
+        Some(TaskError {
+            task: task.to_string(),
+            code: exit_code(status),
+            reason: Reason::Failed,
+        })
+    }
+
+    // Task stopped by a signal sent to tau
+    fn interrupted(task: &str, signal: i32) -> TaskError {
+        TaskError {
+            task: task.to_string(),
+            code: 128 + signal,
+            reason: Reason::Interrupted,
+        }
+    }
+
+    // Failed and timed out tasks can run again
+    fn can_retry(&self) -> bool {
+        self.reason != Reason::Interrupted
+    }
+}
+
+impl Task {
+    fn timeout(&self) -> Result<Option<Duration>> {
+        parse_duration(&self.timeout, "timeout", &self.name)
+    }
+
+    fn retry_delay(&self) -> Result<Duration> {
+        Ok(parse_duration(&self.retry_delay, "retry delay", &self.name)?.unwrap_or_default())
+    }
+}
+
+impl Arg {
+    pub fn is_flag(&self) -> bool {
+        self.flag.unwrap_or(false)
+    }
+
+    pub fn is_positional(&self) -> bool {
+        self.positional.unwrap_or(false)
+    }
+
+    pub fn is_multiple(&self) -> bool {
+        self.multiple.unwrap_or(false)
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+            .unwrap_or(self.default.is_none() && !self.is_flag())
+    }
+
+    // Ask the values of a missing argument
+    pub fn ask(&self) -> Result<Vec<String>> {
+        let theme = ColorfulTheme::default();
+        let prompt = match &self.description {
+            Some(description) => format!("{} ({})", description, self.name),
+            None => self.name.clone(),
+        };
+
+        let Some(choices) = self.choices.as_ref().filter(|choices| !choices.is_empty()) else {
+            let mut input = Input::<String>::with_theme(&theme).with_prompt(prompt);
+
+            if let Some(default) = &self.default {
+                input = input.default(default.clone());
+            }
+
+            let value = input
+                .validate_with(|value: &String| -> Result<(), String> {
+                    for value in self.split(value) {
+                        self.check(&value).map_err(|e| e.to_string())?;
+                    }
+
+                    Ok(())
+                })
+                .interact_text()?;
+
+            return Ok(self.split(&value));
+        };
+
+        if self.is_multiple() {
+            let checked: Vec<bool> = choices
+                .iter()
+                .map(|choice| self.default.as_ref() == Some(choice))
+                .collect();
+
+            let values = MultiSelect::with_theme(&theme)
+                .with_prompt(prompt)
+                .items(choices)
+                .defaults(&checked)
+                .interact()?
+                .into_iter()
+                .map(|option| choices[option].clone())
+                .collect();
+
+            return Ok(values);
+        }
+
+        let default = choices
+            .iter()
+            .position(|choice| self.default.as_ref() == Some(choice))
+            .unwrap_or(0);
+
+        let option = Select::with_theme(&theme)
+            .with_prompt(prompt)
+            .default(default)
+            .items(choices)
+            .interact()?;
+
+        Ok(vec![choices[option].clone()])
+    }
+
+    // Values typed in a prompt, several values are separated by spaces
+    fn split(&self, value: &str) -> Vec<String> {
+        if self.is_multiple() {
+            value.split_whitespace().map(String::from).collect()
+        } else {
+            vec![value.to_string()]
+        }
+    }
+
+    // Check a value of the argument, paths are resolved from the folder where tau was called
+    pub fn check(&self, value: &str) -> Result<String> {
+        if let Some(pattern) = &self.pattern {
+            let regex = Regex::new(&format!("^(?:{})$", pattern)).map_err(|e| {
+                anyhow!(
+                    "Invalid pattern {} of the argument {}: {}",
+                    pattern,
+                    self.name,
+                    e
+                )
+            })?;
+
+            if !regex.is_match(value) {
+                return Err(anyhow!(
+                    "The value {} of the argument {} does not match {}",
+                    value,
+                    self.name,
+                    pattern
+                ));
+            }
+        }
+
+        let kind = self.kind.unwrap_or_default();
+
+        match kind {
+            ArgType::String => Ok(value.to_string()),
+            ArgType::Int => match value.parse::<i64>() {
+                Ok(_) => Ok(value.to_string()),
+                Err(_) => Err(anyhow!(
+                    "The value {} of the argument {} is not an integer",
+                    value,
+                    self.name
+                )),
+            },
+            ArgType::Enum => match &self.choices {
+                Some(choices) if choices.iter().any(|choice| choice == value) => {
+                    Ok(value.to_string())
+                }
+                Some(choices) => Err(anyhow!(
+                    "The value {} of the argument {} is not one of {}",
+                    value,
+                    self.name,
+                    choices.join(", ")
+                )),
+                None => Err(anyhow!(
+                    "The argument {} has the type enum but no choices",
+                    self.name
+                )),
+            },
+            ArgType::Path | ArgType::ExistingFile | ArgType::ExistingDir => {
+                let path = env::current_dir()?.join(value);
+
+                if kind == ArgType::ExistingFile && !path.is_file() {
+                    return Err(anyhow!(
+                        "The file {} of the argument {} does not exist",
+                        path.display(),
+                        self.name
+                    ));
+                }
+
+                if kind == ArgType::ExistingDir && !path.is_dir() {
+                    return Err(anyhow!(
+                        "The folder {} of the argument {} does not exist",
+                        path.display(),
+                        self.name
+                    ));
+                }
+
+                Ok(path.to_string_lossy().to_string())
+            }
+        }
+    }
+
+    // Usage of the argument, like --name <name> or [name]
+    fn usage(&self) -> String {
+        let mut usage = if self.is_positional() {
+            self.name.clone()
+        } else if self.is_flag() {
+            format!("--{}", self.name)
+        } else {
+            format!("--{} <{}>", self.name, self.name)
+        };
+
+        if self.is_multiple() {
+            usage.push_str("...");
+        }
+
+        if self.is_positional() {
+            usage = if self.is_required() {
+                format!("<{}>", usage)
+            } else {
+                format!("[{}]", usage)
+            };
+        }
+
+        usage
+    }
+}
+
+impl CommandProject {
+    // Show the tasks and arguments of the command
+    pub fn display(&self, name: &str) {
+        println!();
+        println!(">> {}", name.bold());
+
+        if let Some(description) = &self.description {
+            println!("{}", format!("// {}", description).dimmed());
+        }
+
+        println!();
+        for task in &self.tasks {
+            let command = match &task.argv {
+                Some(argv) => argv.join(" "),
+                None => task.command.clone(),
+            };
+
+            println!("{}: {}", task.name.bold(), command.cyan());
+        }
+
+        if let Some(args) = &self.args {
+            println!();
+            for arg in args {
+                let mut description = arg.description.clone().unwrap_or_default();
+
+                if let Some(choices) = &arg.choices {
+                    description.push_str(&format!(" [{}]", choices.join(", ")));
+                }
+
+                if let Some(default) = &arg.default {
+                    description.push_str(&format!(" (default: {})", default));
+                }
+
+                println!(
+                    "{} {}",
+                    arg.usage().bold().yellow(),
+                    description.trim().dimmed()
+                );
+            }
+        }
+    }
+}
+
+impl ProjectConfig {
+    // Files that may be missing in a project, including the conditional files of the template
+    pub fn optional_paths(&self, template_path: &Path) -> Vec<String> {
+        let mut optional_paths = self.optional_files.clone();
+
+        if let Ok(Some(manifest)) = Manifest::from(template_path) {
+            optional_paths.extend(manifest.conditions.into_keys());
         }
+
+        optional_paths
     }
 }
 
 impl Config {
     // Update settings
     pub fn update(&self, directory: &Directory) -> Result<()> {
-        let mut writer = BufWriter::new(fs::File::from(
+        let mut writer = BufWriter::new(
             fs::OpenOptions::new()
                 .write(true)
                 .truncate(true)
                 .open(&directory.config)?,
-        ));
+        );
 
         // Save the modification
         writer.write_all(serde_json::to_string_pretty(&self)?.as_bytes())?;
@@ -109,25 +664,171 @@ impl Config {
 
     // Add a project path to the configuration
     pub fn add_route(&mut self, project_path: PathBuf, template_name: String) -> Result<Context> {
-        if let Some(mut project_config) = self.0.remove(&template_name) {
-            let details = Details::from(&project_path);
-            project_config.routes.insert(project_path);
-
-            return Ok(Context {
-                details,
-                project_config,
-                template_name,
-            });
+        Context::from(self, project_path, template_name)
+    }
+
+    // Add a template from a directory
+    pub fn add_template(&mut self, directory: &Directory, name: &str, from: &Path) -> Result<()> {
+        check_template_name(name)?;
+        let template_path = directory.templates.join(name);
+
+        if self.0.contains_key(name) || template_path.exists() {
+            return Err(anyhow!("Template already exists"));
         }
 
-        Err(anyhow!("Project not found"))
+        if !from.is_dir() {
+            return Err(anyhow!("Directory not found"));
+        }
+
+        let mut options = fs_extra::dir::CopyOptions::new();
+        options.content_only = true;
+
+        // A copy that fails halfway leaves no template folder without its configuration
+        if let Err(e) = fs_extra::dir::copy(from, &template_path, &options) {
+            let _ = fs::remove_dir_all(&template_path);
+            return Err(e.into());
+        }
+
+        self.0.insert(
+            name.to_string(),
+            ProjectConfig {
+                optional_files: Vec::new(),
+                commands: HashMap::new(),
+                routes: HashSet::new(),
+                hooks: None,
+                env: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Create a template from a project, without its ignored files and build outputs
+    pub fn create_template(
+        &mut self,
+        directory: &Directory,
+        name: &str,
+        from: &Path,
+    ) -> Result<()> {
+        check_template_name(name)?;
+        let template_path = directory.templates.join(name);
+
+        if self.0.contains_key(name) || template_path.exists() {
+            return Err(anyhow!("Template already exists"));
+        }
+
+        if !from.is_dir() {
+            return Err(anyhow!("Directory not found"));
+        }
+
+        let optional_files = match dir::snapshot_dir(from, &template_path) {
+            Ok(optional_files) => optional_files,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&template_path);
+                return Err(e);
+            }
+        };
+
+        self.0.insert(
+            name.to_string(),
+            ProjectConfig {
+                optional_files,
+                commands: HashMap::new(),
+                routes: HashSet::new(),
+                hooks: None,
+                env: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Remove a template and its configuration
+    pub fn remove_template(&mut self, directory: &Directory, name: &str) -> Result<()> {
+        if self.0.remove(name).is_none() {
+            return Err(anyhow!("Template not found"));
+        }
+
+        let template_path = directory.templates.join(name);
+
+        if template_path.exists() {
+            fs::remove_dir_all(&template_path)?;
+        }
+
+        Ok(())
+    }
+
+    // Rename a template, the registered projects are kept
+    pub fn rename_template(
+        &mut self,
+        directory: &Directory,
+        name: &str,
+        new_name: &str,
+    ) -> Result<()> {
+        check_template_name(new_name)?;
+        let template_path = directory.templates.join(name);
+        let new_template_path = directory.templates.join(new_name);
+
+        if self.0.contains_key(new_name) || new_template_path.exists() {
+            return Err(anyhow!("Template already exists"));
+        }
+
+        match self.0.remove(name) {
+            Some(project_config) => {
+                // The template keeps its name if its folder cannot be moved
+                if let Err(e) = fs::rename(&template_path, &new_template_path) {
+                    self.0.insert(name.to_string(), project_config);
+                    return Err(e.into());
+                }
+
+                self.0.insert(new_name.to_string(), project_config);
+            }
+            None => return Err(anyhow!("Template not found")),
+        }
+
+        Ok(())
+    }
+
+    // Get the configuration of a template
+    pub fn get_template(&mut self, template_name: &str) -> Result<&mut ProjectConfig> {
+        self.0
+            .get_mut(template_name)
+            .ok_or(anyhow!("Template not found"))
+    }
+
+    // Add a command to a template
+    pub fn add_command(
+        &mut self,
+        template_name: &str,
+        name: &str,
+        command_project: CommandProject,
+    ) -> Result<()> {
+        let project_config = self.get_template(template_name)?;
+
+        if project_config.commands.contains_key(name) {
+            return Err(anyhow!("Command already exists"));
+        }
+
+        project_config
+            .commands
+            .insert(name.to_string(), command_project);
+
+        Ok(())
+    }
+
+    // Remove a command from a template
+    pub fn remove_command(&mut self, template_name: &str, name: &str) -> Result<CommandProject> {
+        self.get_template(template_name)?
+            .commands
+            .remove(name)
+            .ok_or(anyhow!("Command not found"))
     }
 
     // Show template names
     pub fn display(&self, directory: &Directory) -> Result<()> {
         println!();
 
-        for (name, _) in &self.0 {
+        for name in self.0.keys() {
             let template_path = directory.templates.join(name);
             let size: f32 = fs_extra::dir::get_size(&template_path)? as f32 / 1024.0;
 
@@ -139,10 +840,37 @@ impl Config {
 }
 
 impl Context {
+    // Create the context of a project, the project configuration is merged over the template.
+    // An invalid project config is reported and the commands of the template are kept
+    fn from(config: &mut Config, project_path: PathBuf, template_name: String) -> Result<Context> {
+        let local_config = LocalConfig::from(&project_path).unwrap_or_else(|e| {
+            eprintln!("{} {}", "Warning:".bold().yellow(), e);
+            LocalConfig::default()
+        });
+        let mut project_config = config
+            .0
+            .remove(&template_name)
+            .ok_or(anyhow!("Project not found"))?;
+        let details = Details::from(&project_path, local_config.variables);
+
+        let mut commands = project_config.commands.clone();
+        commands.extend(local_config.commands);
+
+        project_config.routes.insert(project_path);
+
+        Ok(Context {
+            details,
+            project_config,
+            template_name,
+            commands,
+        })
+    }
+
     // Create a new context
     pub fn new(
         project_name: &String,
         template_name: Option<&String>,
+        hooks: bool,
         directory: &Directory,
         config: &mut Config,
     ) -> Result<Context> {
@@ -175,7 +903,7 @@ impl Context {
                         .default(0)
                         .items(&names)
                         .interact()?;
-                } else if names.len() == 0 {
+                } else if names.is_empty() {
                     return Err(anyhow!("Templates not found"));
                 }
 
@@ -190,30 +918,152 @@ impl Context {
             return Err(anyhow!("Template not found"));
         }
 
-        let mut options = fs_extra::dir::CopyOptions::new();
-        options.content_only = true;
+        if !config.0.contains_key(&template_name) {
+            return Err(anyhow!("Template not found in config"));
+        }
 
-        let _ = copy(&template_path, &project_path, &options);
+        let mut variables = Context::variables(&project_path, &template_name);
+        let mut excluded_files = HashSet::new();
+        let mut answers = Variables::new();
 
-        if let Some(mut project_config) = config.0.remove(&template_name) {
-            println!(
-                "   {} {} ({})",
-                "New project created:".bold().green(),
-                project_name,
-                project_path.display()
-            );
+        if let Some(manifest) = Manifest::from(&template_path)? {
+            answers = manifest.ask(&mut variables)?;
+            excluded_files = manifest.excluded_files(&variables)?;
+        }
 
-            let details = Details::from(&project_path);
-            project_config.routes.insert(project_path);
+        // The project is created in a temporary folder and moved when it is complete,
+        // the current directory is filled in place
+        let in_place = project_name == ".";
+        let staging_path = match (in_place, project_path.file_name()) {
+            (false, Some(name)) => project_path.with_file_name(format!(
+                ".{}.tau-{}",
+                name.to_string_lossy(),
+                process::id()
+            )),
+            _ => project_path.clone(),
+        };
 
-            return Ok(Context {
-                details,
-                project_config,
-                template_name,
-            });
+        let created = (|| -> Result<()> {
+            render_dir(&template_path, &staging_path, &variables, &excluded_files)?;
+
+            // The answers of the template questions are kept in the project
+            if !answers.is_empty() {
+                LocalConfig {
+                    variables: answers,
+                    commands: HashMap::new(),
+                }
+                .save(&staging_path)?;
+            }
+
+            // Hooks run in the temporary folder, the project is only moved once they succeed
+            if hooks {
+                Context::post_create(config, &staging_path, &template_name, &variables)?;
+            }
+
+            if staging_path != project_path {
+                fs::rename(&staging_path, &project_path)?;
+            }
+
+            Ok(())
+        })();
+
+        if let Err(e) = created {
+            Context::rollback(&staging_path, in_place);
+            return Err(e);
+        }
+
+        let context = Context::from(config, project_path.clone(), template_name)?;
+
+        println!(
+            "   {} {} ({})",
+            "New project created:".bold().green(),
+            project_name,
+            project_path.display()
+        );
+
+        Ok(context)
+    }
+
+    // Run the hooks of a new project, with the variables of its final folder
+    fn post_create(
+        config: &mut Config,
+        staging_path: &Path,
+        template_name: &str,
+        variables: &Variables,
+    ) -> Result<()> {
+        let mut context = Context::from(
+            config,
+            staging_path.to_path_buf(),
+            template_name.to_string(),
+        )?;
+
+        if let Some(project_name) = variables.get("project_name") {
+            context
+                .details
+                .variables
+                .insert("project_name".to_string(), project_name.clone());
+        }
+
+        let result = match &context.project_config.hooks {
+            Some(project_hooks) => context.run(
+                &project_hooks.post_create,
+                &RunOptions::default(),
+                None,
+                &HashMap::new(),
+                false,
+            ),
+            None => Ok(()),
+        };
+
+        // The template goes back to the settings without the temporary folder
+        context.project_config.routes.remove(staging_path);
+        config
+            .0
+            .insert(context.template_name, context.project_config);
+
+        result
+    }
+
+    // Remove what was created of a project that failed
+    fn rollback(project_path: &Path, in_place: bool) {
+        if in_place {
+            let _ = dir::clear_dir(project_path);
+        } else {
+            let _ = fs::remove_dir_all(project_path);
         }
+    }
 
-        Err(anyhow!("Template not found in config"))
+    // Variables available when creating a project
+    fn variables(project_path: &Path, template_name: &str) -> Variables {
+        let project_name = project_path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+
+        // The author is taken from git, or from the current user
+        let author = subprocess::Exec::cmd("git")
+            .args(&["config", "user.name"])
+            .stdout(subprocess::Redirection::Pipe)
+            .stderr(subprocess::NullFile)
+            .capture()
+            .ok()
+            .filter(|result| result.success())
+            .map(|result| result.stdout_str().trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| env::var("USER").ok())
+            .or_else(|| env::var("USERNAME").ok())
+            .unwrap_or_default();
+
+        let now = Local::now();
+
+        Variables::from([
+            ("project_name".to_string(), project_name),
+            ("template".to_string(), template_name.to_string()),
+            ("author".to_string(), author),
+            ("date".to_string(), now.format("%Y-%m-%d").to_string()),
+            ("year".to_string(), now.format("%Y").to_string()),
+        ])
     }
 
     // Create a new context from an existing project
@@ -227,54 +1077,41 @@ impl Context {
             dir::up(&home, &project_path, &mut |path| {
                 current_path = path;
 
-                loop {
-                    for (name, project_config) in &mut config.0 {
-                        if let Some(_) = project_config.routes.get(&current_path) {
-                            let template_path = directory.templates.join(&name);
-
-                            match compare_dir(
-                                &template_path,
-                                &current_path,
-                                &project_config.optional_files,
-                            ) {
-                                Ok(true) => {
-                                    template_name = Some(name.clone());
-                                    return Some(());
-                                }
-                                Ok(false) => {
-                                    // If the project was found but the structure is not the same
-                                    project_config.routes.remove(&current_path);
-                                    continue;
-                                }
-                                Err(_) => {}
-                            }
+                for (name, project_config) in &mut config.0 {
+                    if project_config.routes.contains(&current_path) {
+                        let template_path = directory.templates.join(name);
 
-                            return None;
+                        match compare_dir(
+                            &template_path,
+                            &current_path,
+                            &project_config.optional_paths(&template_path),
+                        ) {
+                            Ok(true) => {
+                                template_name = Some(name.clone());
+                                return Some(());
+                            }
+                            Ok(false) => {
+                                // If the project was found but the structure is not the same
+                                project_config.routes.remove(&current_path);
+                                continue;
+                            }
+                            Err(_) => {}
                         }
-                    }
 
-                    break;
+                        return None;
+                    }
                 }
 
                 None
             });
 
             if let Some(template_name) = template_name {
-                if let Some(mut project_config) = config.0.remove(&template_name) {
-                    let details = Details::from(&current_path);
-                    project_config.routes.insert(current_path);
-
-                    return Ok(Context {
-                        details,
-                        project_config,
-                        template_name,
-                    });
-                }
+                return Context::from(config, current_path, template_name);
             }
 
             let mut coincidences = Vec::new();
 
-            let project_path = dir::up(&home, &current_path, &mut |path| {
+            let project_path = dir::up(&home, &project_path, &mut |path| {
                 for (name, project_config) in &config.0 {
                     let template_path = directory.templates.join(name);
 
@@ -283,21 +1120,21 @@ impl Context {
                         return Some(Err(anyhow!("Template not found")));
                     }
 
-                    if let Ok(true) =
-                        compare_dir(&template_path, &path, &project_config.optional_files)
-                    {
+                    let optional_paths = project_config.optional_paths(&template_path);
+
+                    if let Ok(true) = compare_dir(&template_path, &path, &optional_paths) {
                         coincidences.push(name.clone());
                     }
                 }
 
-                if coincidences.len() != 0 {
+                if !coincidences.is_empty() {
                     return Some(Ok(path));
                 }
 
                 None
             });
 
-            if coincidences.len() == 0 {
+            if coincidences.is_empty() {
                 return Err(anyhow!("Project not found"));
             }
 
@@ -323,32 +1160,1052 @@ impl Context {
         Err(anyhow!("Project not found"))
     }
 
+    // Extra arguments go to the end of the last task, unless a task places them with {{args}}
+    pub fn forward_args(&mut self, command: &str) {
+        if let Some(command_project) = self.commands.get_mut(&command.to_lowercase()) {
+            let placed = command_project.tasks.iter().any(|task| {
+                has_variable(&task.command, "args")
+                    || task
+                        .argv
+                        .iter()
+                        .flatten()
+                        .any(|arg| has_variable(arg, "args"))
+            });
+
+            match command_project.tasks.last_mut().filter(|_| !placed) {
+                Some(Task {
+                    argv: Some(argv), ..
+                }) => argv.push("{{args}}".to_string()),
+                Some(task) => task.command.push_str(" {{args}}"),
+                None => {}
+            }
+        }
+    }
+
     // Run a command
-    pub fn exec(&self, command: &str, time: &bool, args: &HashMap<&str, &String>) -> Result<()> {
+    pub fn exec(&self, command: &str, options: &RunOptions, args: &ArgValues) -> Result<()> {
         let command = command.to_lowercase();
 
-        if let Some(command_project) = self.project_config.commands.get(&command.to_string()) {
-            for task in command_project.tasks.iter() {
-                println!("\n{}\n", task.name.bold().cyan());
-                let command = replace_command(&task.command, &self.details, &args)?;
+        if let Some(command_project) = self.commands.get(&command.to_string()) {
+            // Commands become a graph when a task declares its dependencies
+            if command_project
+                .tasks
+                .iter()
+                .any(|task| task.depends_on.is_some())
+            {
+                return self.run_graph(&command, command_project, options, args);
+            }
+
+            let continue_on_error = command_project.continue_on_error.unwrap_or(false);
+            let env = command_project.env.as_ref();
+            return self.run(
+                &command_project.tasks,
+                options,
+                env,
+                args,
+                continue_on_error,
+            );
+        }
+
+        Err(anyhow!("Command not found"))
+    }
+
+    // Run a command each time the files of the project change, stopping the previous run
+    pub fn watch(&self, command: &str, options: &RunOptions, args: &ArgValues) -> Result<()> {
+        let command_project = self
+            .commands
+            .get(&command.to_lowercase())
+            .ok_or(anyhow!("Command not found"))?;
+
+        let workspace = &self.details.workspace;
+        let patterns = command_project.watch.clone().unwrap_or_default();
+
+        // Without patterns the sources are watched, or the whole project without a src folder
+        let root = if patterns.is_empty() && self.details.src.is_dir() {
+            &self.details.src
+        } else {
+            workspace
+        };
+
+        let running = Arc::new(AtomicBool::new(false));
+        let restarted = Arc::new(AtomicBool::new(false));
+
+        let changes = {
+            let running = running.clone();
+            let restarted = restarted.clone();
+
+            Changes::new(root, workspace, &patterns, move || {
+                // Changes while the tasks run stop them, the command runs again after
+                if running.load(Ordering::SeqCst) {
+                    restarted.store(true, Ordering::SeqCst);
+                    restart();
+                }
+            })?
+        };
+
+        catch_signals()?;
+
+        loop {
+            running.store(true, Ordering::SeqCst);
+            let result = self.exec(command, options, args);
+            running.store(false, Ordering::SeqCst);
+            clear_restart();
+
+            if !restarted.swap(false, Ordering::SeqCst) {
+                if let Err(e) = result {
+                    // Ctrl-C stops watching
+                    if e.downcast_ref::<TaskError>()
+                        .is_some_and(|e| e.reason == Reason::Interrupted)
+                    {
+                        return Err(e);
+                    }
+
+                    eprintln!("{} {}", "Error:".bold().red(), e);
+                }
+
+                println!("\n{}", "Waiting for changes...".dimmed());
+            } else {
+                println!("\n{}", "Files changed, restarting...".dimmed());
+            }
+
+            if changes.wait()?.is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    // Run a list of tasks in the workspace, after a failure or a stop signal
+    // only the tasks marked as always are run
+    pub fn run(
+        &self,
+        tasks: &[Task],
+        options: &RunOptions,
+        env: Option<&Env>,
+        args: &ArgValues,
+        continue_on_error: bool,
+    ) -> Result<()> {
+        let mut command_vars = self.environment(options, args)?;
+        self.extend_env(&mut command_vars, env, args)?;
+
+        // Every task is checked before the first one starts
+        let programs = tasks
+            .iter()
+            .map(|task| self.program(task, args))
+            .collect::<Result<Vec<Program>>>()?;
+
+        let cwds = tasks
+            .iter()
+            .map(|task| self.task_cwd(task, args))
+            .collect::<Result<Vec<PathBuf>>>()?;
+
+        let timeouts = tasks
+            .iter()
+            .map(Task::timeout)
+            .collect::<Result<Vec<Option<Duration>>>>()?;
+
+        let retry_delays = tasks
+            .iter()
+            .map(Task::retry_delay)
+            .collect::<Result<Vec<Duration>>>()?;
+
+        let vars = tasks
+            .iter()
+            .map(|task| {
+                let mut vars = command_vars.clone();
+                self.extend_env(&mut vars, task.env.as_ref(), args)?;
+                Ok(vars)
+            })
+            .collect::<Result<Vec<Env>>>()?;
+
+        catch_signals()?;
+
+        let mut failure: Option<TaskError> = None;
+        let mut stopped = false;
+
+        for (position, task) in tasks.iter().enumerate() {
+            // Signals received between two tasks stop the next one
+            if let Some(signal) = take_signal() {
+                failure = Some(TaskError::interrupted(&task.name, signal));
+                stopped = true;
+            }
+
+            if stopped && !task.always.unwrap_or(false) {
+                continue;
+            }
+
+            println!("\n{}\n", task.name.bold().cyan());
+            let (program, cwd, vars) = (&programs[position], &cwds[position], &vars[position]);
+            let (fingerprint, fresh) =
+                self.fingerprint(task, &program.to_string(), vars, cwd, options.force)?;
+
+            if fresh {
+                println!("{}", "Up to date".bold().green());
+                continue;
+            }
+
+            // Interactive tasks get the terminal, other tasks print their output
+            // while they run, keeping its colors, or when they finish
+            let interactive = task.interactive.unwrap_or(false);
+            let buffer = !interactive && task.output.unwrap_or_default() == Output::Buffer;
+            let timeout = timeouts[position];
+            let retry_delay = retry_delays[position];
+            let retries = task.retries.unwrap_or(0);
+            let mut attempt = 0;
+
+            let error = loop {
                 let start = Instant::now();
-                let result = subprocess::Exec::shell(&command)
-                    .cwd(&self.details.workspace)
-                    .stdout(subprocess::Redirection::Pipe)
-                    .stderr(subprocess::Redirection::Merge)
-                    .capture()?;
+                let mut process = Process::spawn(program, cwd, vars, interactive, buffer)?;
+
+                if let Some(timeout) = timeout {
+                    process.set_timeout(timeout);
+                }
+
+                let reader = process
+                    .output()
+                    .map(|output| read_output(output, None, true));
+
+                let status = process.wait()?;
+
+                if let Some(reader) = reader {
+                    println!("{}", reader.join().unwrap_or_default().trim_end());
+                }
+
                 let end = Instant::now();
                 let duration = end.duration_since(start);
-                println!("{}", result.stdout_str().trim());
 
-                if *time {
+                if options.time {
                     println!("\n{}: {} ms", "Time".bold().yellow(), duration.as_millis());
                 }
+
+                let error = TaskError::from(&task.name, status, &process);
+
+                if attempt >= retries || !error.as_ref().is_some_and(TaskError::can_retry) {
+                    break error;
+                }
+
+                attempt += 1;
+                println!(
+                    "\n{} {} ({}/{})\n",
+                    "Retrying:".bold().yellow(),
+                    error.unwrap(),
+                    attempt,
+                    retries
+                );
+
+                // A signal received during the delay stops the task
+                if let Some(signal) = pause(retry_delay) {
+                    break Some(TaskError::interrupted(&task.name, signal));
+                }
+            };
+
+            match error {
+                Some(error) => stopped |= record(&mut failure, error, continue_on_error),
+                None => {
+                    if let Some(fingerprint) = fingerprint {
+                        fingerprint.save()?;
+                    }
+                }
             }
+        }
 
-            return Ok(());
+        match failure {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
         }
+    }
 
-        Err(anyhow!("Command not found"))
+    // Run the tasks of a command as a graph, the tasks whose dependencies finished
+    // run in parallel, up to the number of jobs
+    fn run_graph(
+        &self,
+        name: &str,
+        command_project: &CommandProject,
+        options: &RunOptions,
+        args: &ArgValues,
+    ) -> Result<()> {
+        let mut nodes = Vec::new();
+        self.plan(
+            name,
+            command_project,
+            &mut nodes,
+            &mut HashMap::new(),
+            &mut Vec::new(),
+        )?;
+
+        check_cycles(&nodes)?;
+
+        // Every command is checked before the first task starts
+        let programs = nodes
+            .iter()
+            .map(|node| self.program(node.task, args))
+            .collect::<Result<Vec<Program>>>()?;
+
+        let cwds = nodes
+            .iter()
+            .map(|node| self.task_cwd(node.task, args))
+            .collect::<Result<Vec<PathBuf>>>()?;
+
+        let timeouts = nodes
+            .iter()
+            .map(|node| node.task.timeout())
+            .collect::<Result<Vec<Option<Duration>>>>()?;
+
+        let retry_delays = nodes
+            .iter()
+            .map(|node| node.task.retry_delay())
+            .collect::<Result<Vec<Duration>>>()?;
+
+        // Each task has the variables of its command, then its own variables
+        let base_vars = self.environment(options, args)?;
+        let vars = nodes
+            .iter()
+            .map(|node| {
+                let mut vars = base_vars.clone();
+                self.extend_env(&mut vars, node.env, args)?;
+                self.extend_env(&mut vars, node.task.env.as_ref(), args)?;
+                Ok(vars)
+            })
+            .collect::<Result<Vec<Env>>>()?;
+
+        let jobs = options
+            .jobs
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |jobs| jobs.get()))
+            .max(1);
+        let continue_on_error = command_project.continue_on_error.unwrap_or(false);
+        let width = nodes.iter().map(|node| node.name.len()).max().unwrap_or(0);
+
+        catch_signals()?;
+
+        let mut states = vec![State::Waiting; nodes.len()];
+        // Failed tasks wait for their retry delay before they run again
+        let mut attempts = vec![0; nodes.len()];
+        let mut retry_at: Vec<Option<Instant>> = vec![None; nodes.len()];
+        let mut running: Vec<Job> = Vec::new();
+        let mut failure: Option<TaskError> = None;
+        let mut stopped = false;
+
+        loop {
+            if let Some(signal) = take_signal() {
+                for job in running.iter_mut() {
+                    job.process.signal(signal)?;
+                }
+
+                stopped |= running.iter().any(|job| job.process.interrupted.is_some());
+
+                // Signals received while the tasks wait for their retry stop them
+                let waiting = retry_at.iter().position(Option::is_some);
+
+                if let Some(position) = waiting.filter(|_| running.is_empty()) {
+                    failure = Some(TaskError::interrupted(&nodes[position].name, signal));
+                    stopped = true;
+                }
+            }
+
+            // Start the tasks whose dependencies finished
+            let mut changed = false;
+
+            for (position, node) in nodes.iter().enumerate() {
+                let finished = node.depends_on.iter().all(|&dependency| {
+                    !matches!(states[dependency], State::Waiting | State::Running)
+                });
+
+                if states[position] != State::Waiting || !finished {
+                    continue;
+                }
+
+                let succeeded = node
+                    .depends_on
+                    .iter()
+                    .all(|&dependency| states[dependency] == State::Done);
+
+                if !node.task.always.unwrap_or(false) && (stopped || !succeeded) {
+                    states[position] = State::Skipped;
+                    retry_at[position] = None;
+                    changed = true;
+                    continue;
+                }
+
+                if retry_at[position].is_some_and(|time| time > Instant::now()) {
+                    continue;
+                }
+
+                // Interactive tasks get the terminal for themselves
+                let interactive = node.task.interactive.unwrap_or(false);
+
+                if running.len() >= jobs
+                    || (interactive && !running.is_empty())
+                    || running.iter().any(|job| job.interactive)
+                {
+                    continue;
+                }
+
+                let prefix = format!("{:<width$} |", node.name, width = width)
+                    .color(COLORS[position % COLORS.len()])
+                    .bold()
+                    .to_string();
+
+                let (fingerprint, fresh) = self.fingerprint(
+                    node.task,
+                    &programs[position].to_string(),
+                    &vars[position],
+                    &cwds[position],
+                    options.force,
+                )?;
+
+                if fresh {
+                    println!("{} {}", prefix, "Up to date".bold().green());
+                    states[position] = State::Done;
+                    changed = true;
+                    continue;
+                }
+
+                if interactive {
+                    println!("\n{}\n", node.name.bold().cyan());
+                }
+
+                let buffer = node.task.output.unwrap_or_default() == Output::Buffer;
+                let mut process = Process::spawn(
+                    &programs[position],
+                    &cwds[position],
+                    &vars[position],
+                    interactive,
+                    !interactive,
+                )?;
+
+                if let Some(timeout) = timeouts[position] {
+                    process.set_timeout(timeout);
+                }
+
+                let reader = process
+                    .output()
+                    .map(|output| read_output(output, Some(prefix.clone()), buffer));
+
+                states[position] = State::Running;
+                retry_at[position] = None;
+                running.push(Job {
+                    position,
+                    process,
+                    reader,
+                    fingerprint,
+                    prefix,
+                    interactive,
+                    start: Instant::now(),
+                });
+                changed = true;
+            }
+
+            if running.is_empty() && !changed && retry_at.iter().all(Option::is_none) {
+                break;
+            }
+
+            // Check the running tasks
+            let mut index = 0;
+
+            while index < running.len() {
+                let Some(status) = running[index].process.poll()? else {
+                    index += 1;
+                    continue;
+                };
+
+                let job = running.remove(index);
+                let node = &nodes[job.position];
+                changed = true;
+
+                if let Some(reader) = job.reader {
+                    let output = reader.join().unwrap_or_default();
+
+                    if !output.trim().is_empty() {
+                        println!("{}", output.trim_end());
+                    }
+                }
+
+                if options.time {
+                    let duration = Instant::now().duration_since(job.start);
+                    println!(
+                        "{} {}: {} ms",
+                        job.prefix,
+                        "Time".bold().yellow(),
+                        duration.as_millis()
+                    );
+                }
+
+                let error = TaskError::from(&node.name, status, &job.process);
+                let retries = node.task.retries.unwrap_or(0);
+
+                // The task goes back to waiting, its dependencies are still done
+                if !stopped
+                    && attempts[job.position] < retries
+                    && error.as_ref().is_some_and(TaskError::can_retry)
+                {
+                    attempts[job.position] += 1;
+                    println!(
+                        "{} {} {} ({}/{})",
+                        job.prefix,
+                        "Retrying:".bold().yellow(),
+                        error.unwrap(),
+                        attempts[job.position],
+                        retries
+                    );
+
+                    states[job.position] = State::Waiting;
+                    retry_at[job.position] = Some(Instant::now() + retry_delays[job.position]);
+                    continue;
+                }
+
+                match error {
+                    Some(error) => {
+                        states[job.position] = State::Failed;
+                        stopped |= record(&mut failure, error, continue_on_error);
+                    }
+                    None => {
+                        if let Some(fingerprint) = job.fingerprint {
+                            fingerprint.save()?;
+                        }
+
+                        states[job.position] = State::Done;
+                    }
+                }
+            }
+
+            if !changed {
+                thread::sleep(POLL);
+            }
+        }
+
+        match failure {
+            Some(error) => Err(error.into()),
+            None => Ok(()),
+        }
+    }
+
+    // Environment variables of every task: the .env files of the workspace, which do not
+    // replace the variables of the shell, then the variables of the template
+    fn environment(&self, options: &RunOptions, args: &ArgValues) -> Result<Env> {
+        let mut vars = Env::new();
+        let mut files = vec![(".env".to_string(), false)];
+
+        if let Some(profile) = &options.profile {
+            files.push((format!(".env.{}", profile), true));
+        }
+
+        for (file, required) in files {
+            let path = self.details.workspace.join(&file);
+
+            if !path.exists() {
+                if required {
+                    return Err(anyhow!("The file {} does not exist", file));
+                }
+
+                continue;
+            }
+
+            for item in dotenvy::from_path_iter(&path)? {
+                let (key, value) =
+                    item.map_err(|e| anyhow!("Invalid env file ({}): {}", path.display(), e))?;
+
+                if env::var_os(&key).is_none() {
+                    vars.insert(key, value);
+                }
+            }
+        }
+
+        self.extend_env(&mut vars, self.project_config.env.as_ref(), args)?;
+
+        Ok(vars)
+    }
+
+    // Add the variables of a command or a task, their values can use {{variables}}
+    fn extend_env(&self, vars: &mut Env, env: Option<&Env>, args: &ArgValues) -> Result<()> {
+        for (key, value) in env.into_iter().flatten() {
+            vars.insert(key.clone(), replace_text(value, &self.details, args)?);
+        }
+
+        Ok(())
+    }
+
+    // Command of a task with its variables, or its program and arguments
+    fn program(&self, task: &Task, args: &ArgValues) -> Result<Program> {
+        let Some(argv) = &task.argv else {
+            return Ok(Program::Shell(replace_command(
+                &task.command,
+                &self.details,
+                args,
+            )?));
+        };
+
+        if !task.command.is_empty() {
+            return Err(anyhow!(
+                "The task {} has a command and an argv, it can only have one",
+                task.name
+            ));
+        }
+
+        let argv = replace_argv(argv, &self.details, args)?;
+
+        if argv.is_empty() {
+            return Err(anyhow!("The argv of the task {} is empty", task.name));
+        }
+
+        Ok(Program::Argv(argv))
+    }
+
+    // Folder where a task runs, the workspace by default
+    fn task_cwd(&self, task: &Task, args: &ArgValues) -> Result<PathBuf> {
+        let Some(cwd) = &task.cwd else {
+            return Ok(self.details.workspace.clone());
+        };
+
+        // Absolute paths such as {{src}} or {{cwd}} are kept
+        let cwd = self
+            .details
+            .workspace
+            .join(replace_text(cwd, &self.details, args)?);
+
+        if !cwd.is_dir() {
+            return Err(anyhow!(
+                "The folder {} of the task {} does not exist",
+                cwd.display(),
+                task.name
+            ));
+        }
+
+        Ok(cwd)
+    }
+
+    // Fingerprint of a task with inputs, and whether the task is up to date
+    fn fingerprint(
+        &self,
+        task: &Task,
+        command: &str,
+        env: &Env,
+        cwd: &Path,
+        force: bool,
+    ) -> Result<(Option<Fingerprint>, bool)> {
+        let Some(inputs) = &task.inputs else {
+            return Ok((None, false));
+        };
+
+        let workspace = &self.details.workspace;
+        let fingerprint = Fingerprint::new(workspace, &task.name, command, env, cwd, inputs)?;
+        let outputs = task.outputs.as_deref().unwrap_or_default();
+        let fresh = !force && fingerprint.is_fresh(workspace, outputs)?;
+
+        Ok((Some(fingerprint), fresh))
+    }
+
+    // Add the tasks of a command to the graph, with the commands they depend on,
+    // returns the positions of its tasks
+    fn plan<'a>(
+        &'a self,
+        command: &str,
+        command_project: &'a CommandProject,
+        nodes: &mut Vec<Node<'a>>,
+        planned: &mut HashMap<String, Vec<usize>>,
+        visiting: &mut Vec<String>,
+    ) -> Result<Vec<usize>> {
+        if let Some(positions) = planned.get(command) {
+            return Ok(positions.clone());
+        }
+
+        if visiting.iter().any(|name| name == command) {
+            return Err(anyhow!(
+                "Circular dependency: {} -> {}",
+                visiting.join(" -> "),
+                command
+            ));
+        }
+
+        // Tasks of other commands are named after their command
+        let prefix = if visiting.is_empty() {
+            String::new()
+        } else {
+            format!("{}:", command)
+        };
+
+        visiting.push(command.to_string());
+
+        let tasks = &command_project.tasks;
+        let graph = tasks.iter().any(|task| task.depends_on.is_some());
+        let first = nodes.len();
+
+        for task in tasks {
+            nodes.push(Node {
+                name: format!("{}{}", prefix, task.name),
+                task,
+                env: command_project.env.as_ref(),
+                depends_on: Vec::new(),
+            });
+        }
+
+        for (position, task) in tasks.iter().enumerate() {
+            let mut depends_on = Vec::new();
+
+            // Commands without dependencies keep the order of their tasks
+            if !graph && position > 0 {
+                depends_on.push(first + position - 1);
+            }
+
+            for dependency in task.depends_on.iter().flatten() {
+                if let Some(other) = tasks.iter().position(|task| &task.name == dependency) {
+                    depends_on.push(first + other);
+                } else if let Some(command_project) = self.commands.get(dependency) {
+                    depends_on.extend(self.plan(
+                        dependency,
+                        command_project,
+                        nodes,
+                        planned,
+                        visiting,
+                    )?);
+                } else {
+                    return Err(anyhow!(
+                        "The task {} depends on {}, which is not a task or a command",
+                        task.name,
+                        dependency
+                    ));
+                }
+            }
+
+            nodes[first + position].depends_on = depends_on;
+        }
+
+        visiting.pop();
+
+        let positions: Vec<usize> = (first..first + tasks.len()).collect();
+        planned.insert(command.to_string(), positions.clone());
+
+        Ok(positions)
+    }
+}
+
+// Tasks that depend on each other would never start
+fn check_cycles(nodes: &[Node]) -> Result<()> {
+    let mut settled = vec![false; nodes.len()];
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for (position, node) in nodes.iter().enumerate() {
+            if !settled[position]
+                && node
+                    .depends_on
+                    .iter()
+                    .all(|&dependency| settled[dependency])
+            {
+                settled[position] = true;
+                changed = true;
+            }
+        }
+    }
+
+    let pending: Vec<&str> = nodes
+        .iter()
+        .zip(settled)
+        .filter(|(_, settled)| !settled)
+        .map(|(node, _)| node.name.as_str())
+        .collect();
+
+    if !pending.is_empty() {
+        return Err(anyhow!(
+            "Circular dependency between the tasks {}",
+            pending.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+// Template names are folders of the templates directory, they cannot be paths
+fn check_template_name(name: &str) -> Result<()> {
+    if matches!(name, "" | "." | "..") || name.contains(['/', '\\']) {
+        return Err(anyhow!("Invalid template name {}", name));
+    }
+
+    Ok(())
+}
+
+// Save the error of a task, returns whether the next tasks must stop
+fn record(failure: &mut Option<TaskError>, error: TaskError, continue_on_error: bool) -> bool {
+    if error.reason == Reason::Interrupted {
+        *failure = Some(error);
+        return true;
+    }
+
+    if continue_on_error {
+        println!("{} {}", "Failed:".bold().red(), error);
+    }
+
+    // The first failure gives the exit code of the command
+    failure.get_or_insert(error);
+
+    !continue_on_error
+}
+
+// Read the output of a task while it runs, so the pipe never fills up.
+// The lines are printed with the prefix as they come, or returned when buffered
+fn read_output(output: fs::File, prefix: Option<String>, buffer: bool) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut result = String::new();
+
+        for line in BufReader::new(output).split(b'\n') {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches('\r');
+            let line = match &prefix {
+                Some(prefix) => format!("{} {}", prefix, line),
+                None => line.to_string(),
+            };
+
+            if buffer {
+                result.push_str(&line);
+                result.push('\n');
+            } else {
+                println!("{}", line);
+            }
+        }
+
+        result
+    })
+}
+
+// Duration like 500ms, 30s or 1h 30m
+fn parse_duration(value: &Option<String>, field: &str, task: &str) -> Result<Option<Duration>> {
+    value
+        .as_ref()
+        .map(|value| {
+            humantime::parse_duration(value)
+                .map_err(|e| anyhow!("Invalid {} {} of the task {}: {}", field, value, task, e))
+        })
+        .transpose()
+}
+
+// Exit code of a finished process, signals follow the shell convention
+fn exit_code(status: ExitStatus) -> i32 {
+    match status {
+        ExitStatus::Exited(code) => code as i32,
+        ExitStatus::Signaled(signal) => 128 + signal as i32,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(kind: ArgType) -> Arg {
+        Arg {
+            name: "value".to_string(),
+            kind: Some(kind),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn anchors_the_pattern_of_arguments() {
+        let arg = Arg {
+            pattern: Some("v[0-9]+|latest".to_string()),
+            ..typed(ArgType::String)
+        };
+
+        assert_eq!(arg.check("v12").unwrap(), "v12");
+        assert_eq!(arg.check("latest").unwrap(), "latest");
+        assert!(arg.check("v12-beta").is_err());
+        assert!(arg.check("the latest").is_err());
+
+        let invalid = Arg {
+            pattern: Some("(".to_string()),
+            ..typed(ArgType::String)
+        };
+        assert!(invalid.check("(").is_err());
+    }
+
+    #[test]
+    fn checks_integers() {
+        let arg = typed(ArgType::Int);
+
+        assert_eq!(arg.check("42").unwrap(), "42");
+        assert_eq!(arg.check("-3").unwrap(), "-3");
+        assert!(arg.check("4.2").is_err());
+        assert!(arg.check("ten").is_err());
+        assert!(arg.check("").is_err());
+    }
+
+    #[test]
+    fn checks_the_choices_of_enums() {
+        let arg = Arg {
+            choices: Some(vec!["eu".to_string(), "us".to_string()]),
+            ..typed(ArgType::Enum)
+        };
+
+        assert_eq!(arg.check("eu").unwrap(), "eu");
+        assert!(arg.check("asia").is_err());
+        assert!(typed(ArgType::Enum).check("eu").is_err());
+    }
+
+    #[test]
+    fn resolves_paths_from_the_current_folder() {
+        let cwd = env::current_dir().unwrap();
+        let path = |value| cwd.join(value).to_string_lossy().to_string();
+
+        assert_eq!(
+            typed(ArgType::Path).check("missing/file").unwrap(),
+            path("missing/file")
+        );
+        assert_eq!(
+            typed(ArgType::ExistingFile).check("Cargo.toml").unwrap(),
+            path("Cargo.toml")
+        );
+        assert_eq!(
+            typed(ArgType::ExistingDir).check("src").unwrap(),
+            path("src")
+        );
+
+        let absolute = path("Cargo.toml");
+        assert_eq!(typed(ArgType::Path).check(&absolute).unwrap(), absolute);
+    }
+
+    #[test]
+    fn rejects_missing_files_and_folders() {
+        let file = typed(ArgType::ExistingFile).check("src").unwrap_err();
+        assert!(file.to_string().contains("The file"));
+
+        let dir = typed(ArgType::ExistingDir).check("Cargo.toml").unwrap_err();
+        assert!(dir.to_string().contains("The folder"));
+
+        assert!(typed(ArgType::ExistingFile).check("missing.txt").is_err());
+        assert!(typed(ArgType::ExistingDir).check("missing").is_err());
+    }
+
+    fn task(name: &str, depends_on: Option<&[&str]>) -> Task {
+        Task {
+            name: name.to_string(),
+            command: format!("echo {}", name),
+            depends_on: depends_on.map(|names| names.iter().map(|name| name.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn context(commands: Vec<(&str, Vec<Task>)>) -> Context {
+        let commands: HashMap<String, CommandProject> = commands
+            .into_iter()
+            .map(|(name, tasks)| {
+                let command_project = CommandProject {
+                    tasks,
+                    args: None,
+                    description: None,
+                    continue_on_error: None,
+                    watch: None,
+                    env: None,
+                };
+                (name.to_string(), command_project)
+            })
+            .collect();
+
+        Context {
+            details: Details::from(Path::new("/project"), Variables::new()),
+            project_config: ProjectConfig {
+                optional_files: Vec::new(),
+                commands: commands.clone(),
+                routes: HashSet::new(),
+                hooks: None,
+                env: None,
+            },
+            template_name: "template".to_string(),
+            commands,
+        }
+    }
+
+    // Names of the tasks of a command graph with the names of their dependencies
+    fn graph(context: &Context, command: &str) -> Result<Vec<(String, Vec<String>)>> {
+        let mut nodes = Vec::new();
+        context.plan(
+            command,
+            &context.commands[command],
+            &mut nodes,
+            &mut HashMap::new(),
+            &mut Vec::new(),
+        )?;
+        check_cycles(&nodes)?;
+
+        Ok(nodes
+            .iter()
+            .map(|node| {
+                let depends_on = node.depends_on.iter();
+                let names = depends_on.map(|&other| nodes[other].name.clone());
+                (node.name.clone(), names.collect())
+            })
+            .collect())
+    }
+
+    fn node(name: &str, depends_on: &[&str]) -> (String, Vec<String>) {
+        let depends_on = depends_on.iter().map(|name| name.to_string()).collect();
+        (name.to_string(), depends_on)
+    }
+
+    #[test]
+    fn plans_the_dependencies_between_commands() {
+        let context = context(vec![
+            ("build", vec![task("compile", None), task("link", None)]),
+            (
+                "check",
+                vec![
+                    task("lint", Some(&[])),
+                    task("unit", Some(&["build"])),
+                    task("e2e", Some(&["unit", "build"])),
+                ],
+            ),
+        ]);
+
+        // Commands without depends_on keep the order of their tasks, and are planned once
+        assert_eq!(
+            graph(&context, "check").unwrap(),
+            vec![
+                node("lint", &[]),
+                node("unit", &["build:compile", "build:link"]),
+                node("e2e", &["unit", "build:compile", "build:link"]),
+                node("build:compile", &[]),
+                node("build:link", &["build:compile"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_cycles_between_tasks() {
+        let context = context(vec![(
+            "check",
+            vec![
+                task("lint", Some(&[])),
+                task("a", Some(&["b"])),
+                task("b", Some(&["a"])),
+            ],
+        )]);
+
+        let error = graph(&context, "check").unwrap_err().to_string();
+        assert_eq!(error, "Circular dependency between the tasks a, b");
+    }
+
+    #[test]
+    fn rejects_cycles_between_commands() {
+        let context = context(vec![
+            ("build", vec![task("compile", Some(&["check"]))]),
+            ("check", vec![task("unit", Some(&["build"]))]),
+        ]);
+
+        let error = graph(&context, "check").unwrap_err().to_string();
+        assert_eq!(error, "Circular dependency: check -> build -> check");
+    }
+
+    #[test]
+    fn rejects_unknown_dependencies() {
+        let context = context(vec![("check", vec![task("unit", Some(&["deploy"]))])]);
+
+        let error = graph(&context, "check").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "The task unit depends on deploy, which is not a task or a command"
+        );
     }
 }
@@ -4,7 +4,6 @@ use crate::{
     exec::Commands
 };
 use anyhow::{anyhow, Result};
-use directories;
 use include_dir::{include_dir, Dir};
 use std::{fs, path::PathBuf};
 use colored::Colorize;
@@ -23,6 +22,7 @@ const CONFIG: &str = "config.json";
 const TEMPLATES: &str = "templates";
 
 pub struct Directory {
+    #[allow(dead_code)]
     pub root: PathBuf,
     pub config: PathBuf,
     pub templates: PathBuf,
@@ -38,15 +38,19 @@ impl Directory {
 
             // If the folder does not exist, it is created
             if !path.exists() {
-                fs::create_dir_all(&path)?;
+                fs::create_dir_all(path)?;
             }
 
             let root = path.to_path_buf();
-            let config = path.join(&CONFIG);
-            let templates = path.join(&TEMPLATES);
-            let commands = path.join(&COMMANDS);
+            let config = path.join(CONFIG);
+            let templates = path.join(TEMPLATES);
+            let commands = path.join(COMMANDS);
 
-            rebuild_dir(&PROJECT_DIR, &root)?;
+            // The default files are only created the first time, so that
+            // removed or renamed templates do not come back
+            if !config.exists() {
+                rebuild_dir(&PROJECT_DIR, &root)?;
+            }
 
             return Ok(Directory {
                 root,
@@ -61,12 +65,16 @@ impl Directory {
 
     pub fn get_commands(&self) -> Result<Commands> {
         let commands = fs::read_to_string(&self.commands)?;
-        Ok(serde_json::from_str(&commands)?)
+
+        serde_json::from_str(&commands)
+            .map_err(|e| anyhow!("Invalid commands ({}): {}", self.commands.display(), e))
     }
 
     pub fn get_config(&self) -> Result<Config> {
         let config = fs::read_to_string(&self.config)?;
-        Ok(serde_json::from_str(&config)?)
+
+        serde_json::from_str(&config)
+            .map_err(|e| anyhow!("Invalid config ({}): {}", self.config.display(), e))
     }
 
     pub fn display(&self) -> Result<()> {
@@ -1,16 +1,40 @@
 use anyhow::{anyhow, Result};
-use clap::{Arg, Command};
+use clap::{builder::PossibleValuesParser, Arg, ArgAction, ArgMatches, Command};
 use colored::Colorize;
-use context::Context;
+use context::{ArgValues, CommandProject, Config, Context, RunOptions, Task, TaskError};
+use dialoguer::{theme::ColorfulTheme, Confirm};
 use directory::Directory;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+};
 use utils::string::{persistent_str, persistent_str_optional};
 
 mod context;
 mod directory;
 mod exec;
+mod template;
 mod utils;
 
+// Names used by the tau subcommands
+const RESERVED: [&str; 7] = ["new", "path", "list", "template", "command", "exec", "help"];
+
+// Names and shorts of the options that tau adds to every project command
+const OPTIONS: [&str; 8] = [
+    "time",
+    "force",
+    "watch",
+    "profile",
+    "jobs",
+    "help",
+    "args",
+    "extra_args",
+];
+const SHORTS: [char; 6] = ['t', 'f', 'w', 'p', 'j', 'h'];
+
 fn app() -> Result<()> {
     let mut main = Command::new("Tau")
         .version("0.1.0")
@@ -24,10 +48,115 @@ fn app() -> Result<()> {
                         .value_parser(clap::value_parser!(String))
                         .required(true),
                 )
-                .arg(Arg::new("template_name").help("The project to use")),
+                .arg(Arg::new("template_name").help("The project to use"))
+                .arg(
+                    Arg::new("no_hooks")
+                        .help("Do not run the post create hooks of the template")
+                        .num_args(0)
+                        .long("no-hooks"),
+                ),
         )
         .subcommand(Command::new("path").about("Shows the resource paths used by tau"))
         .subcommand(Command::new("list").about("Shows available templates"))
+        .subcommand(
+            Command::new("template")
+                .about("Manages the templates")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a template from a directory")
+                        .arg(Arg::new("name").help("The name of the template").required(true))
+                        .arg(
+                            Arg::new("from")
+                                .help("The directory to copy")
+                                .long("from")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("create")
+                        .about("Create a template from a project")
+                        .arg(Arg::new("name").help("The name of the template").required(true))
+                        .arg(
+                            Arg::new("from")
+                                .help("The project to copy")
+                                .long("from")
+                                .value_parser(clap::value_parser!(PathBuf))
+                                .default_value("."),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rm")
+                        .about("Remove a template")
+                        .arg(Arg::new("name").help("The name of the template").required(true))
+                        .arg(
+                            Arg::new("yes")
+                                .help("Do not ask for confirmation")
+                                .num_args(0)
+                                .long("yes")
+                                .short('y'),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rename")
+                        .about("Rename a template")
+                        .arg(Arg::new("name").help("The name of the template").required(true))
+                        .arg(Arg::new("new_name").help("The new name").required(true)),
+                )
+                .subcommand(
+                    Command::new("edit")
+                        .about("Open a template with $EDITOR")
+                        .arg(Arg::new("name").help("The name of the template").required(true)),
+                ),
+        )
+        .subcommand(
+            Command::new("command")
+                .about("Manages the commands of the templates")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a command to a template")
+                        .arg(Arg::new("template").help("The name of the template").required(true))
+                        .arg(Arg::new("name").help("The name of the command").required(true))
+                        .arg(
+                            Arg::new("task")
+                                .help("A task to execute, as Name=command")
+                                .long("task")
+                                .action(ArgAction::Append)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("arg")
+                                .help("An argument of the command, as name:description")
+                                .long("arg")
+                                .action(ArgAction::Append),
+                        )
+                        .arg(
+                            Arg::new("description")
+                                .help("The description of the command")
+                                .long("description"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rm")
+                        .about("Remove a command from a template")
+                        .arg(Arg::new("template").help("The name of the template").required(true))
+                        .arg(Arg::new("name").help("The name of the command").required(true)),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Show the commands of a template")
+                        .arg(Arg::new("template").help("The name of the template").required(true))
+                        .arg(Arg::new("name").help("The name of the command")),
+                )
+                .subcommand(
+                    Command::new("edit")
+                        .about("Edit a command with $EDITOR")
+                        .arg(Arg::new("template").help("The name of the template").required(true))
+                        .arg(Arg::new("name").help("The name of the command").required(true)),
+                ),
+        )
         .subcommand(
             Command::new("exec").about("Executes a command").arg(
                 Arg::new("name")
@@ -40,9 +169,12 @@ fn app() -> Result<()> {
     let mut config = directory.get_config()?;
     let context = Context::this(&directory, &mut config);
     let mut commands: HashSet<&str> = HashSet::new();
+    // Commands with invalid arguments fail only when they are called
+    let mut broken: HashMap<&str, anyhow::Error> = HashMap::new();
+    let terminal = io::stdin().is_terminal();
 
     if let Ok(context) = &context {
-        for (name, command_project) in context.project_config.commands.iter() {
+        for (name, command_project) in context.commands.iter() {
             // No need to free memory
             let name: &'static str = persistent_str(name.clone());
             let description = persistent_str_optional(command_project.description.clone());
@@ -55,21 +187,62 @@ fn app() -> Result<()> {
                         .long("time")
                         .short('t'),
                 )
-                .about(&description);
+                .arg(
+                    Arg::new("force")
+                        .help("Run the tasks even if they are up to date")
+                        .num_args(0)
+                        .long("force")
+                        .short('f'),
+                )
+                .arg(
+                    Arg::new("watch")
+                        .help("Run the command again when the files change")
+                        .num_args(0)
+                        .long("watch")
+                        .short('w'),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .help("Load the .env.<profile> file of the project")
+                        .long("profile")
+                        .short('p')
+                        .value_parser(clap::value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("jobs")
+                        .help("Number of tasks that run at the same time")
+                        .long("jobs")
+                        .short('j')
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .about(description);
 
-            if let Some(args) = &command_project.args {
-                for arg in args {
-                    let name: &'static str = persistent_str(arg.name.clone());
-                    let description = persistent_str_optional(arg.description.clone());
+            let args = command_project.args.as_deref().unwrap_or_default();
+
+            match check_args(name, args) {
+                Ok(()) => {
+                    for arg in args {
+                        command = command.arg(command_arg(arg, terminal));
+                    }
 
                     command = command.arg(
-                        Arg::new(name)
-                            .help(&description)
-                            .long(name)
-                            .value_parser(clap::value_parser!(String))
-                            .required(true),
+                        Arg::new("extra_args")
+                            .help("Extra arguments for the command, after --")
+                            .value_name("args")
+                            .num_args(0..)
+                            .allow_hyphen_values(true)
+                            .last(true),
                     );
                 }
+                Err(error) => {
+                    command = Command::new(name).about(description).arg(
+                        Arg::new("extra_args")
+                            .num_args(0..)
+                            .allow_hyphen_values(true)
+                            .trailing_var_arg(true),
+                    );
+                    broken.insert(name, error);
+                }
             }
 
             main = main.subcommand(command);
@@ -85,6 +258,7 @@ fn app() -> Result<()> {
                     .expect("project_name is required");
 
                 let template_name: Option<&String> = args.get_one("template_name");
+                let no_hooks: bool = args.get_flag("no_hooks");
 
                 if let Ok(context) = context {
                     config
@@ -92,14 +266,52 @@ fn app() -> Result<()> {
                         .insert(context.template_name, context.project_config);
                 }
 
-                let context = Context::new(project_name, template_name, &directory, &mut config)?;
+                let context = Context::new(
+                    project_name,
+                    template_name,
+                    !no_hooks,
+                    &directory,
+                    &mut config,
+                )?;
                 config
                     .0
                     .insert(context.template_name, context.project_config);
                 config.update(&directory)?;
             }
+            ("template", args) => {
+                if let Ok(context) = context {
+                    config
+                        .0
+                        .insert(context.template_name, context.project_config);
+                }
+
+                template(args, &directory, &mut config)?;
+                config.update(&directory)?;
+            }
+            ("command", args) => {
+                if let Ok(context) = context {
+                    config
+                        .0
+                        .insert(context.template_name, context.project_config);
+                }
+
+                command(args, &mut config)?;
+
+                // Showing the commands does not change the settings
+                if args.subcommand_name() != Some("show") {
+                    config.update(&directory)?;
+                }
+            }
             ("path", _) => directory.display()?,
-            ("list", _) => config.display(&directory)?,
+            ("list", _) => {
+                if let Ok(context) = context {
+                    config
+                        .0
+                        .insert(context.template_name, context.project_config);
+                }
+
+                config.display(&directory)?
+            }
             ("exec", args) => {
                 let commands = directory.get_commands()?;
                 let name: Option<&String> = args.get_one("name");
@@ -107,14 +319,14 @@ fn app() -> Result<()> {
                 if let Some(name) = name {
                     if let Some(commands) = commands.0.get(name) {
                         for group in commands {
-                            println!("");
+                            println!();
                             println!(">> {}", group.name.bold());
 
                             if let Some(description) = &group.description {
                                 println!("{}", format!("// {}", description).dimmed());
                             }
 
-                            println!("");
+                            println!();
                             for command in &group.commands {
                                 println!("{}", command.bold().cyan());
                             }
@@ -123,7 +335,7 @@ fn app() -> Result<()> {
                         return Err(anyhow!("Command not found"));
                     }
                 } else {
-                    println!("");
+                    println!();
                     for (name, commands) in commands.0.iter() {
                         println!(
                             "{} {}",
@@ -134,35 +346,58 @@ fn app() -> Result<()> {
                 }
             }
             (name, args) => {
-                let context = context?;
+                let mut context = context?;
+
+                if let Some(error) = broken.remove(name) {
+                    return Err(error);
+                }
 
                 if commands.contains(name) {
-                    let time: bool = args.get_flag("time");
+                    let watch: bool = args.get_flag("watch");
+                    let options = RunOptions {
+                        time: args.get_flag("time"),
+                        force: args.get_flag("force"),
+                        jobs: args.get_one("jobs").copied(),
+                        profile: args.get_one("profile").cloned(),
+                    };
 
-                    let mut arguments: HashMap<&str, &String> = HashMap::new();
+                    let extra_args: Vec<String> = args
+                        .get_many("extra_args")
+                        .into_iter()
+                        .flatten()
+                        .cloned()
+                        .collect();
 
-                    if let Some(command_project) = context.project_config.commands.get(name) {
+                    if !extra_args.is_empty() {
+                        context.forward_args(name);
+                    }
+
+                    let mut arguments: ArgValues = HashMap::from([("args", extra_args)]);
+
+                    if let Some(command_project) = context.commands.get(name) {
                         if let Some(command_project_args) = &command_project.args {
                             for arg in command_project_args {
-                                let name: &'static str =
-                                    Box::leak(arg.name.clone().into_boxed_str());
-
-                                if let Some(value) = args.get_one(name) {
-                                    arguments.insert(name, value);
-                                }
+                                arguments.insert(&arg.name, arg_values(arg, args, terminal)?);
                             }
                         }
                     }
 
-                    context.exec(name, &time, &arguments)?;
+                    let result = if watch {
+                        context.watch(name, &options, &arguments)
+                    } else {
+                        context.exec(name, &options, &arguments)
+                    };
+
+                    // The route of the project is saved even if a task failed
+                    config
+                        .0
+                        .insert(context.template_name, context.project_config);
+                    config.update(&directory)?;
+
+                    return result;
                 } else {
                     return Err(anyhow!("Command not found"));
                 }
-
-                config
-                    .0
-                    .insert(context.template_name, context.project_config);
-                config.update(&directory)?;
             }
         };
     };
@@ -170,8 +405,311 @@ fn app() -> Result<()> {
     Ok(())
 }
 
+// Arguments of a project command must not clash with each other or with the
+// options of tau
+fn check_args(command: &str, args: &[context::Arg]) -> Result<()> {
+    let mut names = HashSet::new();
+    let mut shorts = HashSet::new();
+
+    for arg in args {
+        if OPTIONS.contains(&arg.name.as_str()) {
+            return Err(anyhow!(
+                "The argument {} of the command {} is an option of tau, rename it",
+                arg.name,
+                command
+            ));
+        }
+
+        if !names.insert(arg.name.as_str()) {
+            return Err(anyhow!(
+                "The command {} has two arguments named {}",
+                command,
+                arg.name
+            ));
+        }
+
+        let Some(short) = arg.short else {
+            continue;
+        };
+
+        if SHORTS.contains(&short) {
+            return Err(anyhow!(
+                "The short -{} of the argument {} of the command {} is an option of tau, change it",
+                short,
+                arg.name,
+                command
+            ));
+        }
+
+        if !shorts.insert(short) {
+            return Err(anyhow!(
+                "The command {} has two arguments with the short -{}",
+                command,
+                short
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Option of a project command
+fn command_arg(arg: &context::Arg, terminal: bool) -> Arg {
+    let name: &'static str = persistent_str(arg.name.clone());
+    let description = persistent_str_optional(arg.description.clone());
+    let asked = is_asked(arg, terminal);
+    let mut command_arg = Arg::new(name)
+        .help(description)
+        .required(arg.is_required() && !asked);
+
+    if !arg.is_positional() {
+        command_arg = command_arg.long(name);
+    }
+
+    if let Some(short) = arg.short {
+        command_arg = command_arg.short(short);
+    }
+
+    if arg.is_flag() {
+        return command_arg.num_args(0);
+    }
+
+    command_arg = match &arg.choices {
+        Some(choices) => command_arg.value_parser(PossibleValuesParser::new(
+            choices.iter().map(|choice| persistent_str(choice.clone())),
+        )),
+        None => command_arg.value_parser(clap::value_parser!(String)),
+    };
+
+    // The default value of asked arguments is shown in the prompt
+    if let Some(default) = arg.default.as_ref().filter(|_| !asked) {
+        command_arg = command_arg.default_value(persistent_str(default.clone()));
+    }
+
+    if arg.is_multiple() {
+        command_arg = command_arg.num_args(1..).action(ArgAction::Append);
+    }
+
+    command_arg
+}
+
+// Values of an argument in the commands, missing optional arguments have none
+fn arg_values(arg: &context::Arg, args: &ArgMatches, terminal: bool) -> Result<Vec<String>> {
+    if arg.is_flag() {
+        return Ok(vec![args.get_flag(&arg.name).to_string()]);
+    }
+
+    let values = match args.get_many::<String>(&arg.name) {
+        Some(values) => values.cloned().collect(),
+        None if is_asked(arg, terminal) => arg.ask()?,
+        None => Vec::new(),
+    };
+
+    // Values are checked before any task runs
+    values.iter().map(|value| arg.check(value)).collect()
+}
+
+// Missing required arguments are asked in a terminal instead of failing
+fn is_asked(arg: &context::Arg, terminal: bool) -> bool {
+    terminal && arg.is_required() && !arg.is_flag()
+}
+
+// Template management
+fn template(args: &ArgMatches, directory: &Directory, config: &mut Config) -> Result<()> {
+    if let Some((subcommand, args)) = args.subcommand() {
+        let name: &String = args.get_one("name").expect("name is required");
+
+        match subcommand {
+            "add" => {
+                let from: &PathBuf = args.get_one("from").expect("from is required");
+                config.add_template(directory, name, from)?;
+
+                println!("   {} {}", "Template added:".bold().green(), name);
+            }
+            "create" => {
+                let from: &PathBuf = args.get_one("from").expect("from has a default value");
+                config.create_template(directory, name, from)?;
+
+                println!("   {} {}", "Template created:".bold().green(), name);
+
+                if let Some(project_config) = config.0.get(name) {
+                    println!(
+                        "   {} {}",
+                        "Optional files:".bold(),
+                        project_config.optional_files.join(", ")
+                    );
+                }
+            }
+            "rm" => {
+                let confirmed = args.get_flag("yes")
+                    || Confirm::with_theme(&ColorfulTheme::default())
+                        .with_prompt(format!("Remove the template {}?", name))
+                        .default(false)
+                        .interact()?;
+
+                if confirmed {
+                    config.remove_template(directory, name)?;
+                    println!("   {} {}", "Template removed:".bold().green(), name);
+                }
+            }
+            "rename" => {
+                let new_name: &String = args.get_one("new_name").expect("new_name is required");
+                config.rename_template(directory, name, new_name)?;
+
+                println!(
+                    "   {} {} -> {}",
+                    "Template renamed:".bold().green(),
+                    name,
+                    new_name
+                );
+            }
+            "edit" => {
+                if !config.0.contains_key(name) {
+                    return Err(anyhow!("Template not found"));
+                }
+
+                edit(&directory.templates.join(name))?;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+// Command management
+fn command(args: &ArgMatches, config: &mut Config) -> Result<()> {
+    if let Some((subcommand, args)) = args.subcommand() {
+        let template_name: &String = args.get_one("template").expect("template is required");
+        let name: Option<String> = args.get_one::<String>("name").map(|s| s.to_lowercase());
+
+        match (subcommand, name) {
+            ("add", Some(name)) => {
+                if RESERVED.contains(&name.as_str()) {
+                    return Err(anyhow!("The name {} is reserved", name));
+                }
+
+                let mut tasks = Vec::new();
+
+                for task in args.get_many::<String>("task").into_iter().flatten() {
+                    match task.split_once('=') {
+                        Some((name, command)) => tasks.push(Task {
+                            name: name.trim().to_string(),
+                            command: command.trim().to_string(),
+                            ..Default::default()
+                        }),
+                        None => return Err(anyhow!("Invalid task {}, use Name=command", task)),
+                    }
+                }
+
+                let args_project: Vec<context::Arg> = args
+                    .get_many::<String>("arg")
+                    .into_iter()
+                    .flatten()
+                    .map(|arg| match arg.split_once(':') {
+                        Some((name, description)) => context::Arg {
+                            name: name.trim().to_string(),
+                            description: Some(description.trim().to_string()),
+                            ..Default::default()
+                        },
+                        None => context::Arg {
+                            name: arg.trim().to_string(),
+                            ..Default::default()
+                        },
+                    })
+                    .collect();
+
+                check_args(&name, &args_project)?;
+
+                let command_project = CommandProject {
+                    tasks,
+                    args: Some(args_project).filter(|args| !args.is_empty()),
+                    description: args.get_one::<String>("description").cloned(),
+                    continue_on_error: None,
+                    watch: None,
+                    env: None,
+                };
+
+                config.add_command(template_name, &name, command_project)?;
+                println!("   {} {}", "Command added:".bold().green(), name);
+            }
+            ("rm", Some(name)) => {
+                config.remove_command(template_name, &name)?;
+                println!("   {} {}", "Command removed:".bold().green(), name);
+            }
+            ("show", name) => {
+                let project_config = config.get_template(template_name)?;
+                let mut commands: Vec<_> = project_config.commands.iter().collect();
+                commands.sort_by_key(|(name, _)| name.as_str());
+
+                for (command_name, command_project) in commands {
+                    if name.is_none() || name.as_ref() == Some(command_name) {
+                        command_project.display(command_name);
+                    }
+                }
+
+                if let Some(name) = name {
+                    if !project_config.commands.contains_key(&name) {
+                        return Err(anyhow!("Command not found"));
+                    }
+                }
+            }
+            ("edit", Some(name)) => {
+                let command_project = config
+                    .get_template(template_name)?
+                    .commands
+                    .get(&name)
+                    .ok_or(anyhow!("Command not found"))?;
+
+                // The command is edited in a new temporary file, removed when it is dropped,
+                // and validated before saving it
+                let mut file = tempfile::Builder::new()
+                    .prefix("tau-command-")
+                    .suffix(".json")
+                    .tempfile()?;
+                file.write_all(serde_json::to_string_pretty(command_project)?.as_bytes())?;
+                file.flush()?;
+
+                let edited = edit(file.path()).and_then(|_| Ok(fs::read_to_string(file.path())?));
+
+                let command_project: CommandProject = serde_json::from_str(&edited?)
+                    .map_err(|e| anyhow!("Invalid command, it was not saved: {}", e))?;
+
+                config
+                    .get_template(template_name)?
+                    .commands
+                    .insert(name.clone(), command_project);
+
+                println!("   {} {}", "Command updated:".bold().green(), name);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+// Open a file or folder with $EDITOR
+fn edit(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or("vi".to_string());
+
+    let status = subprocess::Exec::shell(format!("{} \"{}\"", editor, path.display())).join()?;
+
+    if !status.success() {
+        return Err(anyhow!("The editor failed"));
+    }
+
+    Ok(())
+}
+
 fn main() {
     if let Err(e) = app() {
         eprintln!("{} {}", "Error:".bold().red(), e);
+
+        // The exit code of a failed task is kept
+        let code = e.downcast_ref::<TaskError>().map_or(1, |e| e.code);
+        process::exit(code);
     }
 }
@@ -0,0 +1,241 @@
+use crate::{context::Variables, utils::render::evaluate};
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+// Template manifest file
+pub const MANIFEST: &str = "tau.template.json";
+
+// Question asked when creating a project
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Question {
+    Text {
+        name: String,
+        prompt: String,
+        default: Option<String>,
+        pattern: Option<String>,
+    },
+    Select {
+        name: String,
+        prompt: String,
+        choices: Vec<String>,
+        default: Option<String>,
+    },
+    Confirm {
+        name: String,
+        prompt: String,
+        default: Option<bool>,
+    },
+    MultiSelect {
+        name: String,
+        prompt: String,
+        choices: Vec<String>,
+        default: Option<Vec<String>>,
+    },
+}
+
+// Template options
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Manifest {
+    #[serde(default)]
+    pub questions: Vec<Question>,
+    // Files and folders that are only created when their condition holds
+    #[serde(default)]
+    pub conditions: BTreeMap<String, String>,
+}
+
+impl Question {
+    pub fn name(&self) -> &String {
+        match self {
+            Question::Text { name, .. }
+            | Question::Select { name, .. }
+            | Question::Confirm { name, .. }
+            | Question::MultiSelect { name, .. } => name,
+        }
+    }
+
+    // Ask the question and get the answer as a variable
+    pub fn ask(&self) -> Result<String> {
+        let theme = ColorfulTheme::default();
+
+        let answer = match self {
+            Question::Text {
+                prompt,
+                default,
+                pattern,
+                ..
+            } => {
+                let pattern = match pattern {
+                    Some(pattern) => Some(Regex::new(&format!("^(?:{})$", pattern))?),
+                    None => None,
+                };
+
+                let mut input = Input::<String>::with_theme(&theme).with_prompt(prompt);
+
+                if let Some(default) = default {
+                    input = input.default(default.clone());
+                }
+
+                input
+                    .validate_with(|value: &String| -> Result<(), String> {
+                        match &pattern {
+                            Some(pattern) if !pattern.is_match(value) => {
+                                Err(format!("The value must match {}", pattern.as_str()))
+                            }
+                            _ => Ok(()),
+                        }
+                    })
+                    .interact_text()?
+            }
+            Question::Select {
+                prompt,
+                choices,
+                default,
+                ..
+            } => {
+                let option = Select::with_theme(&theme)
+                    .with_prompt(prompt)
+                    .default(self.position(choices, default.as_ref())?)
+                    .items(choices)
+                    .interact()?;
+
+                choices[option].clone()
+            }
+            Question::Confirm {
+                prompt, default, ..
+            } => Confirm::with_theme(&theme)
+                .with_prompt(prompt)
+                .default(default.unwrap_or(false))
+                .interact()?
+                .to_string(),
+            Question::MultiSelect {
+                prompt,
+                choices,
+                default,
+                ..
+            } => {
+                let mut checked = vec![false; choices.len()];
+
+                for value in default.iter().flatten() {
+                    checked[self.position(choices, Some(value))?] = true;
+                }
+
+                MultiSelect::with_theme(&theme)
+                    .with_prompt(prompt)
+                    .items(choices)
+                    .defaults(&checked)
+                    .interact()?
+                    .into_iter()
+                    .map(|option| choices[option].as_str())
+                    .collect::<Vec<&str>>()
+                    .join(",")
+            }
+        };
+
+        Ok(answer)
+    }
+
+    // Answer used when the questions cannot be asked
+    pub fn default_answer(&self) -> Result<String> {
+        let answer = match self {
+            Question::Text { default, .. } => default.clone().unwrap_or_default(),
+            Question::Select {
+                choices, default, ..
+            } => choices[self.position(choices, default.as_ref())?].clone(),
+            Question::Confirm { default, .. } => default.unwrap_or(false).to_string(),
+            Question::MultiSelect {
+                choices, default, ..
+            } => {
+                for value in default.iter().flatten() {
+                    self.position(choices, Some(value))?;
+                }
+
+                default.clone().unwrap_or_default().join(",")
+            }
+        };
+
+        Ok(answer)
+    }
+
+    // Position of a choice in the list
+    fn position(&self, choices: &[String], value: Option<&String>) -> Result<usize> {
+        if choices.is_empty() {
+            return Err(anyhow!("The question {} has no choices", self.name()));
+        }
+
+        match value {
+            Some(value) => choices
+                .iter()
+                .position(|choice| choice == value)
+                .ok_or(anyhow!(
+                    "The default value {} of the question {} is not a choice",
+                    value,
+                    self.name()
+                )),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Manifest {
+    // Get the manifest of a template, if it has one
+    pub fn from(template_path: &Path) -> Result<Option<Manifest>> {
+        let path = template_path.join(MANIFEST);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let manifest = fs::read_to_string(&path)?;
+
+        match serde_json::from_str(&manifest) {
+            Ok(manifest) => Ok(Some(manifest)),
+            Err(e) => Err(anyhow!(
+                "Invalid template manifest ({}): {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    // Ask the questions, the answers are added to the variables
+    pub fn ask(&self, variables: &mut Variables) -> Result<Variables> {
+        let mut answers = Variables::new();
+        // Without a terminal, as in scripts, the default answers are used
+        let terminal = io::stdin().is_terminal();
+
+        for question in &self.questions {
+            let answer = if terminal {
+                question.ask()?
+            } else {
+                question.default_answer()?
+            };
+
+            variables.insert(question.name().clone(), answer.clone());
+            answers.insert(question.name().clone(), answer);
+        }
+
+        Ok(answers)
+    }
+
+    // Files whose condition does not hold with the variables
+    pub fn excluded_files(&self, variables: &Variables) -> Result<HashSet<PathBuf>> {
+        let mut excluded_files = HashSet::new();
+
+        for (path, condition) in &self.conditions {
+            if !evaluate(condition, variables)? {
+                excluded_files.insert(PathBuf::from(path));
+            }
+        }
+
+        Ok(excluded_files)
+    }
+}
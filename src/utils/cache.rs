@@ -0,0 +1,192 @@
+use crate::context::Env;
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Fingerprints of the tasks, inside the workspace
+pub const CACHE_DIR: &str = ".tau/cache";
+
+// Hash of the inputs of a task, saved after the task succeeds
+pub struct Fingerprint {
+    path: PathBuf,
+    hash: String,
+}
+
+impl Fingerprint {
+    // Hash the command of a task, its variables and its folder with the files that
+    // match its inputs
+    pub fn new(
+        workspace: &Path,
+        name: &str,
+        command: &str,
+        env: &Env,
+        cwd: &Path,
+        inputs: &[String],
+    ) -> Result<Fingerprint> {
+        let mut hasher = Sha256::new();
+        hasher.update(command.as_bytes());
+
+        for (key, value) in env {
+            hasher.update(format!("\0{}={}", key, value).as_bytes());
+        }
+
+        let cwd = cwd.strip_prefix(workspace).unwrap_or(cwd);
+        hasher.update(format!("\0{}\0", cwd.display()).as_bytes());
+
+        for path in expand(workspace, inputs)? {
+            if !path.is_file() {
+                continue;
+            }
+
+            // Renamed files change the hash too
+            let relative = path.strip_prefix(workspace).unwrap_or(&path);
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update(Sha256::digest(fs::read(&path)?));
+        }
+
+        // Tasks with the same name in different commands have their own fingerprint
+        let key = hex(&Sha256::digest(format!("{}\0{}", name, command).as_bytes()));
+
+        Ok(Fingerprint {
+            path: workspace.join(CACHE_DIR).join(&key[..16]),
+            hash: hex(&hasher.finalize()),
+        })
+    }
+
+    // The inputs did not change since the last success and the outputs still exist
+    pub fn is_fresh(&self, workspace: &Path, outputs: &[String]) -> Result<bool> {
+        match fs::read_to_string(&self.path) {
+            Ok(hash) if hash.trim() == self.hash => {}
+            _ => return Ok(false),
+        }
+
+        for output in outputs {
+            if expand(workspace, std::slice::from_ref(output))?.is_empty() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Save the fingerprint after the task succeeds
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&self.path, &self.hash)?;
+
+        Ok(())
+    }
+}
+
+// Files that match the glob patterns, which are relative to the workspace
+fn expand(workspace: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let workspace = glob::Pattern::escape(&workspace.to_string_lossy());
+    let mut paths = Vec::new();
+
+    for pattern in patterns {
+        let matches = glob::glob(&format!("{}/{}", workspace, pattern))
+            .map_err(|e| anyhow!("Invalid pattern {}: {}", pattern, e))?;
+
+        for path in matches {
+            paths.push(path?);
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> Vec<String> {
+        vec!["src/*.c".to_string()]
+    }
+
+    fn fingerprint(workspace: &Path, env: &Env, cwd: &str) -> Fingerprint {
+        Fingerprint::new(
+            workspace,
+            "build",
+            "make",
+            env,
+            &workspace.join(cwd),
+            &inputs(),
+        )
+        .unwrap()
+    }
+
+    fn workspace() -> tempfile::TempDir {
+        let workspace = tempfile::tempdir().unwrap();
+        fs::create_dir(workspace.path().join("src")).unwrap();
+        fs::write(workspace.path().join("src/main.c"), "int main() {}").unwrap();
+        workspace
+    }
+
+    #[test]
+    fn changes_with_the_inputs() {
+        let workspace = workspace();
+        let path = workspace.path();
+        let before = fingerprint(path, &Env::new(), "");
+
+        fs::write(path.join("src/main.c"), "int main() { return 1; }").unwrap();
+        let edited = fingerprint(path, &Env::new(), "");
+        assert_ne!(before.hash, edited.hash);
+
+        fs::write(path.join("src/util.c"), "").unwrap();
+        let added = fingerprint(path, &Env::new(), "");
+        assert_ne!(edited.hash, added.hash);
+
+        // Files that do not match the inputs are ignored
+        fs::write(path.join("README.md"), "").unwrap();
+        assert_eq!(added.hash, fingerprint(path, &Env::new(), "").hash);
+    }
+
+    #[test]
+    fn changes_with_the_env_and_the_cwd() {
+        let workspace = workspace();
+        let path = workspace.path();
+        let env = Env::from([("MODE".to_string(), "debug".to_string())]);
+        let base = fingerprint(path, &env, "");
+
+        let release = Env::from([("MODE".to_string(), "release".to_string())]);
+        assert_ne!(base.hash, fingerprint(path, &release, "").hash);
+        assert_ne!(base.hash, fingerprint(path, &Env::new(), "").hash);
+        assert_ne!(base.hash, fingerprint(path, &env, "src").hash);
+        assert_eq!(base.hash, fingerprint(path, &env, "").hash);
+    }
+
+    #[test]
+    fn is_fresh_after_saving_while_the_outputs_exist() {
+        let workspace = workspace();
+        let path = workspace.path();
+        let outputs = vec!["build/*.o".to_string()];
+        let before = fingerprint(path, &Env::new(), "");
+
+        assert!(!before.is_fresh(path, &[]).unwrap());
+        before.save().unwrap();
+        assert!(before.is_fresh(path, &[]).unwrap());
+
+        // A missing output makes the task run again
+        assert!(!before.is_fresh(path, &outputs).unwrap());
+        fs::create_dir(path.join("build")).unwrap();
+        fs::write(path.join("build/main.o"), "").unwrap();
+        assert!(before.is_fresh(path, &outputs).unwrap());
+
+        fs::write(path.join("src/main.c"), "").unwrap();
+        let edited = fingerprint(path, &Env::new(), "");
+        assert!(!edited.is_fresh(path, &outputs).unwrap());
+    }
+}
@@ -1,7 +1,21 @@
-use anyhow::Result;
+use crate::{
+    context::{Variables, LOCAL_CONFIG},
+    template::MANIFEST,
+    utils::{
+        render::render_text,
+        replace::{replace_variables, variables_pattern},
+    },
+};
+use anyhow::{anyhow, Result};
+use ignore::WalkBuilder;
 use include_dir;
 use lazy_static::lazy_static;
-use std::{collections::HashSet, fs, path::PathBuf};
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
 
 // Junk files without pattern
 const JUNK_FILES: [&str; 15] = [
@@ -22,12 +36,54 @@ const JUNK_FILES: [&str; 15] = [
     "swapfile.sys"
 ];
 
+// Build outputs and dependencies that are not part of a template
+pub const BUILD_DIRS: [&str; 12] = [
+    ".git",
+    ".tau",
+    "build",
+    "node_modules",
+    ".venv",
+    "venv",
+    "__pycache__",
+    ".pytest_cache",
+    "target",
+    "dist",
+    "zig-cache",
+    "zig-out"
+];
+
+// Files that projects usually change or remove
+const OPTIONAL_FILES: [&str; 14] = [
+    ".gitignore",
+    ".tauignore",
+    ".editorconfig",
+    ".vscode",
+    ".idea",
+    "README.md",
+    "LICENSE",
+    "CHANGELOG.md",
+    "docs",
+    "test",
+    "tests",
+    "bun.lockb",
+    "package-lock.json",
+    "go.sum"
+];
+
+// Ignore file of tau, with the same format as .gitignore
+const TAUIGNORE: &str = ".tauignore";
+
 lazy_static! {
     static ref JUNK_FILES_SET: HashSet<String> = JUNK_FILES.iter().map(|s| s.to_string()).collect();
 }
 
+// Junk files created by the system
+pub fn is_junk(name: &str) -> bool {
+    JUNK_FILES_SET.contains(name)
+}
+
 // Walk through a directory
-pub fn walk_dir<F, T>(dir: &PathBuf, mut callback: F) -> Result<()>
+pub fn walk_dir<F, T>(dir: &Path, mut callback: F) -> Result<()>
 where
     F: FnMut(PathBuf) -> T,
 {
@@ -39,66 +95,88 @@ where
 }
 
 // Compare the contents of two directories
-pub fn compare_dir(dir: &PathBuf, other: &PathBuf, optional_files: &Vec<String>) -> Result<bool> {
-    let mut queue: Vec<(PathBuf, PathBuf)> = Vec::new();
-    queue.push((dir.clone(), other.clone()));
-
-    let mut optional_files: HashSet<PathBuf> = optional_files.iter().map(PathBuf::from).collect();
-
-    while queue.len() > 0 {
-        if let Some((current_dir, current_other)) = queue.pop() {
-            // Folder and file names in the current directory
-            let mut sub_dir: HashSet<PathBuf> = HashSet::new();
-            let mut sub_other: HashSet<PathBuf> = HashSet::new();
-
-            // Base
-            walk_dir(&current_dir, |current| -> Result<()> {
-                // Ignore junk files
-                if let Some(name) = current.file_name().and_then(|s| s.to_str()) {
-                    if JUNK_FILES_SET.contains(name) {
-                        return Ok(());
-                    }
-                }
+pub fn compare_dir(dir: &Path, other: &Path, optional_files: &[String]) -> Result<bool> {
+    let optional_files: HashSet<PathBuf> = optional_files.iter().map(PathBuf::from).collect();
 
-                // Get relative path
-                if let Ok(new_current) = current.strip_prefix(dir) {
-                    let new_current = new_current.to_path_buf();
+    compare_level(dir, dir, other, &optional_files)
+}
 
-                    // If it is optional, it is not considered in the set
-                    if optional_files.contains(&new_current) {
-                        optional_files.remove(&new_current);
-                    } else {
-                        sub_dir.insert(new_current);
-                    }
-                }
+// Compare one level of the template with the project, going down into the folders
+fn compare_level(
+    root: &Path,
+    current_dir: &Path,
+    current_other: &Path,
+    optional_files: &HashSet<PathBuf>,
+) -> Result<bool> {
+    // Folder and file names in the current directory
+    let mut sub_dir: Vec<PathBuf> = Vec::new();
+    let mut sub_other: HashSet<OsString> = HashSet::new();
 
-                Ok(())
-            })?;
+    // Base
+    walk_dir(current_dir, |current| {
+        // Ignore junk files and the template manifest
+        if let Some(name) = current.file_name().and_then(|s| s.to_str()) {
+            if JUNK_FILES_SET.contains(name) || name == MANIFEST {
+                return;
+            }
+        }
 
-            // Muestra
-            walk_dir(&current_other, |current| {
-                if let Ok(new_current) = current.strip_prefix(other) {
-                    sub_other.insert(new_current.to_path_buf());
-                }
-            })?;
+        // If it is optional, it is not considered in the set
+        if let Ok(relative) = current.strip_prefix(root) {
+            if !optional_files.contains(relative) {
+                sub_dir.push(current);
+            }
+        }
+    })?;
+
+    // Muestra
+    walk_dir(current_other, |current| {
+        if let Some(name) = current.file_name() {
+            sub_other.insert(name.to_os_string());
+        }
+    })?;
+
+    // Check if the current level is the same
+    for path in sub_dir {
+        let name = match path.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        // Templated names match any name with the same shape
+        if let Some(pattern) = name.to_str().and_then(variables_pattern) {
+            let mut found = false;
 
-            // Check if the current level is the same
-            if sub_dir.is_subset(&sub_other) {
-                for path in sub_dir {
-                    let new_dir = current_dir.join(&path);
-                    let new_other = current_other.join(&path);
+            for candidate in sub_other.iter().filter_map(|s| s.to_str()) {
+                let new_other = current_other.join(candidate);
+
+                if !pattern.is_match(candidate) || path.is_dir() != new_other.is_dir() {
+                    continue;
+                }
 
-                    if new_dir.is_dir() {
-                        queue.push((new_dir, new_other));
-                    }
+                if !path.is_dir() || compare_level(root, &path, &new_other, optional_files)? {
+                    found = true;
+                    break;
                 }
-            } else {
+            }
+
+            if !found {
+                return Ok(false);
+            }
+        } else {
+            if !sub_other.contains(name) {
+                return Ok(false);
+            }
+
+            if path.is_dir()
+                && !compare_level(root, &path, &current_other.join(name), optional_files)?
+            {
                 return Ok(false);
             }
         }
     }
 
-    return Ok(true);
+    Ok(true)
 }
 
 // Deeply rebuild a directory found in the binary
@@ -112,7 +190,7 @@ pub fn rebuild_dir(from: &include_dir::Dir, to: &PathBuf) -> Result<()> {
                     fs::create_dir_all(&path)?;
                 }
 
-                rebuild_dir(dir, &to)?;
+                rebuild_dir(dir, to)?;
             }
             include_dir::DirEntry::File(file) => {
                 let path = to.join(file.path());
@@ -127,29 +205,156 @@ pub fn rebuild_dir(from: &include_dir::Dir, to: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-// Move between project folders
-pub fn up<F, T>(home: &PathBuf, current_dir: &PathBuf, callback: &mut F) -> Option<T>
-where
-    F: FnMut(PathBuf) -> Option<T>,
-{
-    let mut current = Some(current_dir.as_path());
+// Copy a template directory, rendering the text files with the variables
+pub fn render_dir(
+    from: &Path,
+    to: &Path,
+    variables: &Variables,
+    excluded_files: &HashSet<PathBuf>,
+) -> Result<()> {
+    render_level(from, from, to, variables, excluded_files)
+}
+
+// Copy one level of the template, going down into the folders
+fn render_level(
+    root: &Path,
+    from: &Path,
+    to: &Path,
+    variables: &Variables,
+    excluded_files: &HashSet<PathBuf>,
+) -> Result<()> {
+    if !to.exists() {
+        fs::create_dir_all(to)?;
+    }
+
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
 
-    loop {
-        if let Some(path) = current {
-            // The project must be inside HOME
-            if path == home {
-                break;
+        let name = match path.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+
+        // Ignore junk files and the template manifest
+        if let Some(name) = name.to_str() {
+            if JUNK_FILES_SET.contains(name) || name == MANIFEST {
+                continue;
             }
+        }
+
+        // Files excluded by the template conditions are not created
+        if let Ok(relative) = path.strip_prefix(root) {
+            if excluded_files.contains(relative) {
+                continue;
+            }
+        }
 
-            if let Some(result) = callback(path.to_path_buf()) {
-                return Some(result);
-            } // If it is None, continue
+        // Templated names are expanded with the variables
+        let target = match name.to_str() {
+            Some(name) => to.join(replace_variables(name, variables)),
+            None => to.join(name),
+        };
 
-            current = path.parent();
+        if path.is_dir() {
+            render_level(root, &path, &target, variables, excluded_files)?;
         } else {
+            let contents = fs::read(&path)?;
+
+            match String::from_utf8(contents) {
+                // Binary files are copied as they are
+                Ok(text) if !text.contains('\0') => {
+                    let text = render_text(&text, variables)
+                        .map_err(|e| anyhow!("{} ({})", e, path.display()))?;
+
+                    fs::write(&target, text)?;
+                }
+                Ok(text) => fs::write(&target, text)?,
+                Err(e) => fs::write(&target, e.into_bytes())?,
+            }
+
+            fs::set_permissions(&target, fs::metadata(&path)?.permissions())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Copy a project directory without ignored files, build outputs and dependencies,
+// returning the suggested optional files
+pub fn snapshot_dir(from: &Path, to: &Path) -> Result<Vec<String>> {
+    let walker = WalkBuilder::new(from)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(TAUIGNORE)
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+
+            // The local configuration keeps the answers of the original project
+            !(JUNK_FILES_SET.contains(name.as_ref())
+                || BUILD_DIRS.contains(&name.as_ref())
+                || name == LOCAL_CONFIG)
+        })
+        .build();
+
+    let mut optional_files = Vec::new();
+
+    for entry in walker {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(from)?;
+        let target = to.join(relative);
+
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+
+        if let Some(name) = relative.to_str() {
+            if OPTIONAL_FILES.contains(&name) {
+                optional_files.push(name.to_string());
+            }
+        }
+    }
+
+    optional_files.sort();
+
+    Ok(optional_files)
+}
+
+// Remove the contents of a directory, keeping the directory
+pub fn clear_dir(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Move between project folders
+pub fn up<F, T>(home: &Path, current_dir: &Path, callback: &mut F) -> Option<T>
+where
+    F: FnMut(PathBuf) -> Option<T>,
+{
+    let mut current = Some(current_dir);
+
+    while let Some(path) = current {
+        // The project must be inside HOME
+        if path == home {
             break;
         }
+
+        if let Some(result) = callback(path.to_path_buf()) {
+            return Some(result);
+        } // If it is None, continue
+
+        current = path.parent();
     }
 
-    return None;
+    None
 }
\ No newline at end of file
@@ -1,3 +1,7 @@
+pub mod cache;
 pub mod dir;
+pub mod process;
+pub mod render;
 pub mod replace;
-pub mod string;
\ No newline at end of file
+pub mod string;
+pub mod watch;
\ No newline at end of file
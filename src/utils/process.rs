@@ -0,0 +1,283 @@
+use crate::{context::Env, utils::replace::quote};
+use anyhow::Result;
+use itertools::Itertools;
+use lazy_static::lazy_static;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    fmt,
+    fs::File,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use subprocess::{ExitStatus, Popen, PopenConfig, Redirection};
+
+// Signals that stop tau, they are forwarded to the running task
+#[cfg(unix)]
+const SIGNALS: [i32; 3] = [SIGINT, SIGTERM, signal_hook::consts::SIGHUP];
+#[cfg(not(unix))]
+const SIGNALS: [i32; 2] = [SIGINT, SIGTERM];
+
+// Signal that kills a task which does not stop after its timeout
+#[cfg(unix)]
+const KILL: i32 = signal_hook::consts::SIGKILL;
+#[cfg(not(unix))]
+const KILL: i32 = SIGTERM;
+
+// Time given to a task to stop after its timeout, before it is killed
+const GRACE: Duration = Duration::from_secs(2);
+
+// Saved instead of a signal to restart a command
+const RESTART: usize = usize::MAX;
+
+// Time between checks of a running task
+pub const POLL: Duration = Duration::from_millis(100);
+
+lazy_static! {
+    static ref SIGNAL: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+    static ref HANDLER: Mutex<bool> = Mutex::new(false);
+}
+
+// Keep tau alive on the stop signals, they are saved until the running task is checked
+pub fn catch_signals() -> Result<()> {
+    let mut installed = HANDLER.lock().unwrap();
+
+    if !*installed {
+        for signal in SIGNALS {
+            signal_hook::flag::register_usize(signal, SIGNAL.clone(), signal as usize)?;
+        }
+
+        *installed = true;
+    }
+
+    Ok(())
+}
+
+// Last stop signal received, if any
+pub fn take_signal() -> Option<i32> {
+    match SIGNAL.swap(0, Ordering::SeqCst) {
+        0 => None,
+        // The running tasks are stopped as if tau received SIGTERM
+        RESTART => Some(SIGTERM),
+        signal => Some(signal as i32),
+    }
+}
+
+// Stop the running tasks to run the command again, real signals go first
+pub fn restart() {
+    let _ = SIGNAL.compare_exchange(0, RESTART, Ordering::SeqCst, Ordering::SeqCst);
+}
+
+// Forget a restart that came after the tasks finished
+pub fn clear_restart() {
+    let _ = SIGNAL.compare_exchange(RESTART, 0, Ordering::SeqCst, Ordering::SeqCst);
+}
+
+// Wait for a while, returns the signal if tau is stopped before
+pub fn pause(duration: Duration) -> Option<i32> {
+    let end = Instant::now() + duration;
+
+    loop {
+        if let Some(signal) = take_signal() {
+            return Some(signal);
+        }
+
+        let left = end.saturating_duration_since(Instant::now());
+
+        if left.is_zero() {
+            return None;
+        }
+
+        thread::sleep(left.min(POLL));
+    }
+}
+
+// What a task runs, a command of the shell or a program with its arguments
+pub enum Program {
+    Shell(String),
+    Argv(Vec<String>),
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Program::Shell(command) => write!(f, "{}", command),
+            Program::Argv(argv) => write!(f, "{}", argv.iter().map(|arg| quote(arg)).join(" ")),
+        }
+    }
+}
+
+// Running command of a task
+pub struct Process {
+    child: Popen,
+    // The process group of a task has the id of its first process
+    group: Option<u32>,
+    interactive: bool,
+    // Signal that stopped the process
+    pub interrupted: Option<i32>,
+    timeout: Option<Duration>,
+    // The process is stopped after the deadline, and killed after the next one
+    deadline: Option<Instant>,
+    // Timeout that stopped the process
+    pub timed_out: Option<Duration>,
+}
+
+impl Process {
+    // Start a command with the shell of the system or a program, capturing its output if asked
+    pub fn spawn(
+        program: &Program,
+        cwd: &Path,
+        env: &Env,
+        interactive: bool,
+        capture: bool,
+    ) -> Result<Process> {
+        // Only interactive tasks read from the terminal
+        let stdin = if interactive {
+            Redirection::None
+        } else {
+            Redirection::File(File::open(if cfg!(windows) { "nul" } else { "/dev/null" })?)
+        };
+
+        let (stdout, stderr) = if capture {
+            (Redirection::Pipe, Redirection::Merge)
+        } else {
+            (Redirection::None, Redirection::None)
+        };
+
+        let config = PopenConfig {
+            stdin,
+            stdout,
+            stderr,
+            cwd: Some(cwd.as_os_str().to_owned()),
+            // The variables of the task are added to the ones of tau
+            env: Some(
+                std::env::vars_os()
+                    .chain(env.iter().map(|(key, value)| (key.into(), value.into())))
+                    .collect(),
+            ),
+            // Other tasks get their own process group, so the whole tree can be stopped
+            #[cfg(unix)]
+            setpgid: !interactive,
+            ..Default::default()
+        };
+
+        let argv = match program {
+            Program::Shell(command) if cfg!(windows) => vec!["cmd.exe", "/c", command],
+            Program::Shell(command) => vec!["sh", "-c", command],
+            Program::Argv(argv) => argv.iter().map(String::as_str).collect(),
+        };
+
+        let child = Popen::create(&argv, config)?;
+
+        Ok(Process {
+            group: child.pid(),
+            child,
+            interactive,
+            interrupted: None,
+            timeout: None,
+            deadline: None,
+            timed_out: None,
+        })
+    }
+
+    // Stop the process if it runs for longer than the timeout
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+        self.deadline = Some(Instant::now() + timeout);
+    }
+
+    // Captured output of the process
+    pub fn output(&mut self) -> Option<File> {
+        self.child.stdout.take()
+    }
+
+    // Check if the process finished
+    pub fn poll(&mut self) -> Result<Option<ExitStatus>> {
+        let status = self.child.poll();
+
+        match status {
+            // Processes left in the background by a stopped task are stopped too
+            Some(_) if self.is_stopped() && !self.interactive => self.forward(SIGTERM)?,
+            Some(_) => {}
+            None => self.check_deadline()?,
+        }
+
+        Ok(status)
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.interrupted.is_some() || self.timed_out.is_some()
+    }
+
+    // Stop the process when its time is over, and kill it if it does not stop
+    fn check_deadline(&mut self) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {}
+            _ => return Ok(()),
+        }
+
+        if self.timed_out.is_none() {
+            self.timed_out = self.timeout;
+            self.deadline = Some(Instant::now() + GRACE);
+            self.forward(SIGTERM)
+        } else {
+            self.deadline = None;
+            self.forward(KILL)
+        }
+    }
+
+    // Wait for the process, forwarding the stop signals
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        loop {
+            self.child.wait_timeout(POLL)?;
+
+            // A signal received while the process finished belongs to it
+            if let Some(signal) = take_signal() {
+                self.signal(signal)?;
+            }
+
+            if let Some(status) = self.poll()? {
+                return Ok(status);
+            }
+        }
+    }
+
+    // Send a stop signal to the processes of the task
+    pub fn signal(&mut self, signal: i32) -> Result<()> {
+        // The terminal already sent Ctrl-C to interactive tasks, they decide what to do
+        if self.interactive && signal == SIGINT {
+            return Ok(());
+        }
+
+        self.interrupted = Some(signal);
+        self.forward(signal)
+    }
+
+    #[cfg(unix)]
+    fn forward(&mut self, signal: i32) -> Result<()> {
+        if let Some(pid) = self.group {
+            // Interactive tasks share the process group of tau
+            let target = if self.interactive {
+                pid as i32
+            } else {
+                -(pid as i32)
+            };
+
+            unsafe {
+                libc::kill(target, signal);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn forward(&mut self, _signal: i32) -> Result<()> {
+        self.child.terminate()?;
+        Ok(())
+    }
+}
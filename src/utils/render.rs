@@ -0,0 +1,410 @@
+use crate::{context::Variables, utils::replace::replace_variables};
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// Blocks, a block alone on its line takes the whole line
+const BLOCK: &str = r"(?m)^[ \t]*\{%\s*(.+?)\s*%\}[ \t]*\r?\n|\{%\s*(.+?)\s*%\}";
+const TOKEN: &str = r#""[^"]*"|'[^']*'|==|!=|&&|\|\||!|\(|\)|[^\s()!=&|]+"#;
+
+lazy_static! {
+    static ref BLOCK_RE: Regex = Regex::new(BLOCK).unwrap();
+    static ref TOKEN_RE: Regex = Regex::new(TOKEN).unwrap();
+}
+
+// Parts of a text
+enum Node {
+    Text(String),
+    If(Vec<(String, Vec<Node>)>, Vec<Node>),
+    For(String, String, Vec<Node>),
+    // Text written as it is, without variables
+    Raw(String),
+}
+
+// Render the blocks and the variables of a text
+pub fn render_text(text: &str, variables: &Variables) -> Result<String> {
+    // Texts without blocks only have variables
+    if !BLOCK_RE.is_match(text) {
+        return Ok(replace_variables(text, variables));
+    }
+
+    let mut tags = Vec::new();
+    let mut last = 0;
+
+    for caps in BLOCK_RE.captures_iter(text) {
+        let (Some(whole), Some(tag)) = (caps.get(0), caps.get(1).or(caps.get(2))) else {
+            continue;
+        };
+
+        tags.push((
+            text[last..whole.start()].to_string(),
+            tag.as_str().to_string(),
+            whole.as_str().to_string(),
+        ));
+        last = whole.end();
+    }
+
+    let mut tags = tags.into_iter();
+    let (nodes, end) = parse(&mut tags, &text[last..])?;
+
+    if let Some(end) = end {
+        return Err(anyhow!("Unexpected block {{% {} %}}", end));
+    }
+
+    let mut result = String::new();
+    render(&nodes, variables, &mut result)?;
+
+    Ok(result)
+}
+
+// Build the nodes until a closing block is found
+fn parse<I>(tags: &mut I, rest: &str) -> Result<(Vec<Node>, Option<String>)>
+where
+    I: Iterator<Item = (String, String, String)>,
+{
+    let mut nodes = Vec::new();
+
+    while let Some((text, tag, whole)) = tags.next() {
+        nodes.push(Node::Text(text));
+
+        let (keyword, expression) = match tag.split_once(char::is_whitespace) {
+            Some((keyword, expression)) => (keyword, expression.trim()),
+            None => (tag.as_str(), ""),
+        };
+
+        match keyword {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut otherwise = Vec::new();
+                let mut condition = expression.to_string();
+
+                loop {
+                    let (body, end) = parse(tags, "")?;
+                    let end = end.ok_or(anyhow!("Missing {{% endif %}}"))?;
+
+                    match end.split_once(char::is_whitespace) {
+                        Some(("elif", expression)) => {
+                            branches.push((condition, body));
+                            condition = expression.trim().to_string();
+                        }
+                        _ if end == "else" => {
+                            branches.push((condition, body));
+                            let (body, end) = parse(tags, "")?;
+
+                            if end.as_deref() != Some("endif") {
+                                return Err(anyhow!("Missing {{% endif %}}"));
+                            }
+
+                            otherwise = body;
+                            break;
+                        }
+                        _ if end == "endif" => {
+                            branches.push((condition, body));
+                            break;
+                        }
+                        _ => return Err(anyhow!("Unexpected block {{% {} %}}", end)),
+                    }
+                }
+
+                nodes.push(Node::If(branches, otherwise));
+            }
+            "for" => {
+                let (item, list) = match expression.split_once(" in ") {
+                    Some((item, list)) => (item.trim(), list.trim()),
+                    None => return Err(anyhow!("Invalid block {{% {} %}}", tag)),
+                };
+
+                let (body, end) = parse(tags, "")?;
+
+                if end.as_deref() != Some("endfor") {
+                    return Err(anyhow!("Missing {{% endfor %}}"));
+                }
+
+                nodes.push(Node::For(item.to_string(), list.to_string(), body));
+            }
+            "raw" => {
+                let mut raw = String::new();
+
+                loop {
+                    let (text, tag, whole) =
+                        tags.next().ok_or(anyhow!("Missing {{% endraw %}}"))?;
+                    raw.push_str(&text);
+
+                    if tag == "endraw" {
+                        break;
+                    }
+
+                    raw.push_str(&whole);
+                }
+
+                nodes.push(Node::Raw(raw));
+            }
+            "elif" | "else" | "endif" | "endfor" | "endraw" => return Ok((nodes, Some(tag))),
+            // Blocks of other template languages, like {% extends %}, are kept
+            _ => nodes.push(Node::Text(whole)),
+        }
+    }
+
+    nodes.push(Node::Text(rest.to_string()));
+
+    Ok((nodes, None))
+}
+
+// Write the nodes with the values of the variables
+fn render(nodes: &[Node], variables: &Variables, result: &mut String) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => result.push_str(&replace_variables(text, variables)),
+            Node::Raw(text) => result.push_str(text),
+            Node::If(branches, otherwise) => {
+                let mut body = otherwise;
+
+                for (condition, branch) in branches {
+                    if evaluate(condition, variables)? {
+                        body = branch;
+                        break;
+                    }
+                }
+
+                render(body, variables, result)?;
+            }
+            Node::For(item, list, body) => {
+                let mut variables = variables.clone();
+
+                for value in values(variables.get(list).map(String::as_str).unwrap_or("")) {
+                    variables.insert(item.clone(), value);
+                    render(body, &variables, result)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Values of a list variable
+fn values(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// Evaluate a condition such as `with_tests`, `!with_tests`, `license == "MIT"`,
+// `"cli" in features`, combined with `&&`, `||` and parentheses
+pub fn evaluate(condition: &str, variables: &Variables) -> Result<bool> {
+    let tokens: Vec<&str> = TOKEN_RE.find_iter(condition).map(|m| m.as_str()).collect();
+
+    let mut position = 0;
+    let result = or(&tokens, &mut position, variables)?;
+
+    if position != tokens.len() {
+        return Err(anyhow!("Invalid condition: {}", condition));
+    }
+
+    Ok(result)
+}
+
+fn or(tokens: &[&str], position: &mut usize, variables: &Variables) -> Result<bool> {
+    let mut result = and(tokens, position, variables)?;
+
+    while tokens.get(*position) == Some(&"||") {
+        *position += 1;
+        result |= and(tokens, position, variables)?;
+    }
+
+    Ok(result)
+}
+
+fn and(tokens: &[&str], position: &mut usize, variables: &Variables) -> Result<bool> {
+    let mut result = not(tokens, position, variables)?;
+
+    while tokens.get(*position) == Some(&"&&") {
+        *position += 1;
+        result &= not(tokens, position, variables)?;
+    }
+
+    Ok(result)
+}
+
+fn not(tokens: &[&str], position: &mut usize, variables: &Variables) -> Result<bool> {
+    match tokens.get(*position) {
+        Some(&"!") => {
+            *position += 1;
+            Ok(!not(tokens, position, variables)?)
+        }
+        Some(&"(") => {
+            *position += 1;
+            let result = or(tokens, position, variables)?;
+
+            if tokens.get(*position) != Some(&")") {
+                return Err(anyhow!("Missing ) in condition"));
+            }
+
+            *position += 1;
+            Ok(result)
+        }
+        _ => comparison(tokens, position, variables),
+    }
+}
+
+fn comparison(tokens: &[&str], position: &mut usize, variables: &Variables) -> Result<bool> {
+    let left = value(tokens, position, variables)?;
+
+    let result = match tokens.get(*position) {
+        Some(&"==") => {
+            *position += 1;
+            left == value(tokens, position, variables)?
+        }
+        Some(&"!=") => {
+            *position += 1;
+            left != value(tokens, position, variables)?
+        }
+        Some(&"in") => {
+            *position += 1;
+            values(&value(tokens, position, variables)?).contains(&left)
+        }
+        _ => !left.is_empty() && left != "false",
+    };
+
+    Ok(result)
+}
+
+// Quoted strings, booleans and numbers are literals, other words are variables
+fn value(tokens: &[&str], position: &mut usize, variables: &Variables) -> Result<String> {
+    let token = *tokens
+        .get(*position)
+        .ok_or(anyhow!("Incomplete condition"))?;
+
+    *position += 1;
+
+    if token.len() >= 2 && (token.starts_with('"') || token.starts_with('\'')) {
+        return Ok(token[1..token.len() - 1].to_string());
+    }
+
+    if token == "true" || token == "false" || token.parse::<f64>().is_ok() {
+        return Ok(token.to_string());
+    }
+
+    // Variables without value are empty
+    Ok(variables.get(token).cloned().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> Variables {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn render(text: &str, pairs: &[(&str, &str)]) -> String {
+        render_text(text, &variables(pairs)).unwrap()
+    }
+
+    #[test]
+    fn replaces_variables() {
+        let text = "name = {{ project_name }}, {{unknown}}";
+        assert_eq!(
+            render(text, &[("project_name", "tau")]),
+            "name = tau, {{unknown}}"
+        );
+    }
+
+    #[test]
+    fn renders_nested_if_elif_else() {
+        let text = "{% if a %}\n{% if b %}\nab\n{% else %}\na\n{% endif %}\n\
+                    {% elif c %}\nc\n{% else %}\nnone\n{% endif %}\n";
+
+        assert_eq!(render(text, &[("a", "true"), ("b", "true")]), "ab\n");
+        assert_eq!(render(text, &[("a", "true"), ("b", "false")]), "a\n");
+        assert_eq!(render(text, &[("a", "false"), ("c", "true")]), "c\n");
+        assert_eq!(render(text, &[]), "none\n");
+    }
+
+    #[test]
+    fn renders_for_over_lists() {
+        let text = "{% for feature in features %}- {{feature}}\n{% endfor %}";
+
+        assert_eq!(
+            render(text, &[("features", "cli, http,")]),
+            "- cli\n- http\n"
+        );
+        assert_eq!(render(text, &[("features", "")]), "");
+        assert_eq!(render(text, &[]), "");
+    }
+
+    #[test]
+    fn keeps_inline_blocks_on_their_line() {
+        let text = "license: {% if license %}{{license}}{% else %}none{% endif %}\n";
+        assert_eq!(render(text, &[("license", "MIT")]), "license: MIT\n");
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        let vars = variables(&[
+            ("license", "MIT"),
+            ("features", "cli,http"),
+            ("with_tests", "true"),
+            ("empty", ""),
+        ]);
+        let check = |condition| evaluate(condition, &vars).unwrap();
+
+        assert!(check("with_tests"));
+        assert!(!check("!with_tests"));
+        assert!(!check("empty"));
+        assert!(!check("missing"));
+        assert!(check(r#"license == "MIT""#));
+        assert!(check("license != 'ISC'"));
+        assert!(check(r#""cli" in features"#));
+        assert!(!check(r#""db" in features"#));
+        assert!(check(r#"!("db" in features) && with_tests"#));
+        assert!(check(r#"empty || (license == "MIT" && !empty)"#));
+        assert!(!check("!(with_tests || empty)"));
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        let vars = variables(&[]);
+
+        assert!(evaluate("(a && b", &vars).is_err());
+        assert!(evaluate("a ==", &vars).is_err());
+        assert!(evaluate("a b", &vars).is_err());
+    }
+
+    #[test]
+    fn rejects_unclosed_and_stray_blocks() {
+        let vars = variables(&[]);
+
+        assert!(render_text("{% if a %}text", &vars).is_err());
+        assert!(render_text("{% if a %}text{% else %}other", &vars).is_err());
+        assert!(render_text("{% for x in list %}text", &vars).is_err());
+        assert!(render_text("{% for x %}text{% endfor %}", &vars).is_err());
+        assert!(render_text("text{% endif %}", &vars).is_err());
+        assert!(render_text("{% if a %}text{% endfor %}", &vars).is_err());
+        assert!(render_text("{% raw %}text", &vars).is_err());
+    }
+
+    #[test]
+    fn keeps_unknown_blocks() {
+        let text = "{% extends \"base.html\" %}\n{% block content %}{{title}}{% endblock %}\n";
+        assert_eq!(
+            render(text, &[("title", "Home")]),
+            "{% extends \"base.html\" %}\n{% block content %}Home{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_raw_blocks() {
+        let text = "{% raw %}\n{% if user %}{{ user.name }}{% endif %}\n{% endraw %}\n{{name}}";
+        assert_eq!(
+            render(text, &[("name", "tau"), ("user", "x")]),
+            "{% if user %}{{ user.name }}{% endif %}\ntau"
+        );
+    }
+}
@@ -1,62 +1,69 @@
-use crate::context::Details;
+use crate::context::{ArgValues, Details, Variables};
 use anyhow::{anyhow, Result};
 use colored::Colorize;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::HashMap;
 
 // Variables
-const WORKSPACE: &str = r"\{\{workspace\}\}";
-const SRC: &str = r"\{\{src\}\}";
 const ARG: &str = r"\{\{(.+?)\}\}";
-const CASES: [&str; 3] = [WORKSPACE, SRC, ARG];
+// Variables of the commands, raw values are not quoted
+const COMMAND_ARG: &str = r"\{\{(raw:)?(.+?)\}\}";
 
 // Regular expression to search for variables
 lazy_static! {
-    static ref RE: Regex = {
-        let pattern = CASES.iter().join("|");
-        Regex::new(&pattern).unwrap()
-    };
+    static ref RE: Regex = Regex::new(COMMAND_ARG).unwrap();
+    static ref VARIABLE_RE: Regex = Regex::new(ARG).unwrap();
+}
+
+// Add the necessary variables to the command, quoting their values for the shell
+pub fn replace_command(command: &str, details: &Details, args: &ArgValues) -> Result<String> {
+    replace(command, details, args, true)
+}
+
+// Add the variables to a value that does not go through the shell, like a folder
+pub fn replace_text(text: &str, details: &Details, args: &ArgValues) -> Result<String> {
+    replace(text, details, args, false)
+}
+
+// Add the variables to the arguments of a program, an argument that is only a
+// variable becomes one argument per value
+pub fn replace_argv(argv: &[String], details: &Details, args: &ArgValues) -> Result<Vec<String>> {
+    let mut result = Vec::new();
+
+    for arg in argv {
+        match RE.captures(arg) {
+            Some(caps) if caps[0].len() == arg.len() => {
+                result.extend(values(&caps[2], details, args, arg)?);
+            }
+            _ => result.push(replace_text(arg, details, args)?),
+        }
+    }
+
+    Ok(result)
 }
 
-// Add the necessary variables to the command
-pub fn replace_command(command: &String, details: &Details, args: &HashMap<&str, &String>) -> Result<String> {
+fn replace(
+    command: &str,
+    details: &Details,
+    args: &ArgValues,
+    quote_values: bool,
+) -> Result<String> {
     let mut result = Ok(());
 
     let replaced = RE.replace_all(command, |caps: &regex::Captures| {
-        if let Some(m) = caps.get(0) {
-            let value = match m.as_str() {
-                "{{workspace}}" => details.workspace.to_str(),
-                "{{src}}" => details.src.to_str(),
-                arg if arg.starts_with("{{") => {
-                    if let Some(name) = caps.get(1) {
-                        let name = name.as_str();
-
-                        if let Some(value) = args.get(name) {
-                            return value.to_string();
-                        } else {
-                            // The argument requested in the command does not exist
-                            result = Err(anyhow!(format!(
-                                "{}\n{} {}",
-                                "the argument is missing or does not exist",
-                                "Command:".yellow().bold(),
-                                command
-                            )));
-                        }
-                    }
-
-                    None
-                }
-                _ => None,
-            };
-
-            if let Some(value) = value.and_then(|s| Some(s.to_string())) {
-                return value;
+        let raw = caps.get(1).is_some();
+
+        match values(&caps[2], details, args, command) {
+            Ok(values) if quote_values && !raw => {
+                values.iter().map(|value| quote(value)).join(" ")
+            }
+            Ok(values) => values.join(" "),
+            Err(e) => {
+                result = Err(e);
+                String::new()
             }
         }
-
-        String::new()
     });
 
     match result {
@@ -64,3 +71,190 @@ pub fn replace_command(command: &String, details: &Details, args: &HashMap<&str,
         Err(e) => Err(e),
     }
 }
+
+// Whether a text has a variable, quoted or raw
+pub fn has_variable(text: &str, name: &str) -> bool {
+    RE.captures_iter(text).any(|caps| &caps[2] == name)
+}
+
+// Values of a variable, arguments can have several values or none
+fn values(name: &str, details: &Details, args: &ArgValues, command: &str) -> Result<Vec<String>> {
+    let path = match name {
+        "workspace" => Some(&details.workspace),
+        "src" => Some(&details.src),
+        "cwd" => Some(&details.cwd),
+        _ => None,
+    };
+
+    if let Some(path) = path {
+        return Ok(vec![path.to_string_lossy().to_string()]);
+    }
+
+    if let Some(values) = args.get(name) {
+        Ok(values.clone())
+    } else if let Some(value) = details.variables.get(name) {
+        Ok(vec![value.clone()])
+    } else {
+        // The argument requested in the command does not exist
+        Err(anyhow!(format!(
+            "{}\n{} {}",
+            "the argument is missing or does not exist",
+            "Command:".yellow().bold(),
+            command
+        )))
+    }
+}
+
+// Quote a value for the shell, values without special characters are kept as they are.
+// On Windows the quotes are a best effort, cmd.exe still expands %VAR% inside them
+pub fn quote(value: &str) -> String {
+    let special = if cfg!(windows) { "-_./=:,+@" } else { "-_./=:,+@%" };
+    let safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || special.contains(c));
+
+    if safe {
+        value.to_string()
+    } else if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+// Add the template variables to a text, unknown variables are left as they are
+pub fn replace_variables(text: &str, variables: &Variables) -> String {
+    VARIABLE_RE
+        .replace_all(text, |caps: &regex::Captures| {
+            let name = caps[1].trim();
+
+            match variables.get(name) {
+                Some(value) => value.clone(),
+                None => caps[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+// Pattern that matches any value of the variables in a text
+pub fn variables_pattern(text: &str) -> Option<Regex> {
+    if !VARIABLE_RE.is_match(text) {
+        return None;
+    }
+
+    let pattern = VARIABLE_RE.split(text).map(regex::escape).join(".+");
+
+    Regex::new(&format!("^{}$", pattern)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn details() -> Details {
+        Details {
+            workspace: PathBuf::from("/work/my app"),
+            src: PathBuf::from("/work/my app/src"),
+            cwd: PathBuf::from("/work/my app"),
+            variables: Variables::from([("name".to_string(), "tau".to_string())]),
+        }
+    }
+
+    fn args<'a>(pairs: &[(&'a str, &[&str])]) -> ArgValues<'a> {
+        pairs
+            .iter()
+            .map(|(name, values)| (*name, values.iter().map(|v| v.to_string()).collect()))
+            .collect()
+    }
+
+    fn argv(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quotes_values_for_the_shell() {
+        assert_eq!(quote("main.rs"), "main.rs");
+        assert_eq!(quote("--level=2"), "--level=2");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("my file"), "'my file'");
+        assert_eq!(quote("a; rm -rf /"), "'a; rm -rf /'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quotes_each_value_of_the_commands() {
+        let details = details();
+        let args = args(&[("files", &["a b", "c;d", "e"]), ("empty", &[""])]);
+        let replace = |command| replace_command(command, &details, &args).unwrap();
+
+        assert_eq!(replace("cat {{files}}"), "cat 'a b' 'c;d' e");
+        assert_eq!(replace("echo {{empty}}"), "echo ''");
+        assert_eq!(replace("echo {{raw:files}}"), "echo a b c;d e");
+        assert_eq!(replace("cd {{workspace}}"), "cd '/work/my app'");
+        assert_eq!(replace("echo {{name}}"), "echo tau");
+    }
+
+    #[test]
+    fn replaces_text_without_quotes() {
+        let details = details();
+        let args = args(&[("dir", &["my dir"])]);
+
+        assert_eq!(
+            replace_text("{{workspace}}/{{dir}}", &details, &args).unwrap(),
+            "/work/my app/my dir"
+        );
+    }
+
+    #[test]
+    fn spreads_the_values_of_the_argv() {
+        let details = details();
+        let args = args(&[
+            ("files", &["a b", "it's"]),
+            ("none", &[]),
+            ("empty", &[""]),
+            ("cmd", &["x; y"]),
+        ]);
+        let replace = |items: &[&str]| replace_argv(&argv(items), &details, &args).unwrap();
+
+        assert_eq!(
+            replace(&["cat", "{{files}}"]),
+            argv(&["cat", "a b", "it's"])
+        );
+        assert_eq!(
+            replace(&["cat", "{{raw:files}}"]),
+            argv(&["cat", "a b", "it's"])
+        );
+        assert_eq!(replace(&["ls", "{{none}}"]), argv(&["ls"]));
+        assert_eq!(replace(&["echo", "{{empty}}"]), argv(&["echo", ""]));
+        assert_eq!(
+            replace(&["sh", "-c", "{{cmd}}"]),
+            argv(&["sh", "-c", "x; y"])
+        );
+        assert_eq!(
+            replace(&["--files={{files}}", "{{name}}"]),
+            argv(&["--files=a b it's", "tau"])
+        );
+    }
+
+    #[test]
+    fn fails_on_missing_arguments() {
+        let details = details();
+        let args = args(&[]);
+
+        assert!(replace_command("echo {{missing}}", &details, &args).is_err());
+        assert!(replace_argv(&argv(&["{{missing}}"]), &details, &args).is_err());
+    }
+
+    #[test]
+    fn finds_quoted_and_raw_variables() {
+        assert!(has_variable("cargo test {{args}}", "args"));
+        assert!(has_variable("cargo test {{raw:args}}", "args"));
+        assert!(!has_variable("cargo test {{argsx}}", "args"));
+        assert!(!has_variable("cargo test", "args"));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::utils::{
+    dir::{is_junk, BUILD_DIRS},
+    process::{take_signal, POLL},
+};
+use anyhow::{anyhow, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+// Quiet time after a change before the command runs again
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// Changes of the files of a project
+pub struct Changes {
+    // Stops watching when dropped
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<()>,
+}
+
+// Paths whose changes are not followed
+struct Filter {
+    workspace: PathBuf,
+    gitignore: Gitignore,
+    patterns: Vec<glob::Pattern>,
+}
+
+impl Changes {
+    // Watch a folder of the workspace, only the paths that match the patterns count if
+    // there are patterns. The callback is called on each change
+    pub fn new<F>(
+        root: &Path,
+        workspace: &Path,
+        patterns: &[String],
+        on_change: F,
+    ) -> Result<Changes>
+    where
+        F: Fn() + Send + 'static,
+    {
+        let filter = Filter::new(workspace, patterns)?;
+        let (sender, receiver) = mpsc::channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+
+            // Reading a file is not a change
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                return;
+            }
+
+            if event.paths.iter().any(|path| filter.matches(path)) {
+                on_change();
+                let _ = sender.send(());
+            }
+        })?;
+
+        watcher.watch(root, RecursiveMode::Recursive)?;
+
+        Ok(Changes {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    // Wait for a change and until the changes stop, returns the signal if tau is stopped before
+    pub fn wait(&self) -> Result<Option<i32>> {
+        loop {
+            match self.receiver.recv_timeout(POLL) {
+                Ok(()) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(signal) = take_signal() {
+                        return Ok(Some(signal));
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(anyhow!("The watcher stopped")),
+            }
+        }
+
+        // Saving several files at once runs the command once
+        while self.receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+        Ok(None)
+    }
+}
+
+impl Filter {
+    fn new(workspace: &Path, patterns: &[String]) -> Result<Filter> {
+        let mut builder = GitignoreBuilder::new(workspace);
+        builder.add(workspace.join(".gitignore"));
+
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .map_err(|e| anyhow!("Invalid pattern {}: {}", pattern, e))
+            })
+            .collect::<Result<Vec<glob::Pattern>>>()?;
+
+        Ok(Filter {
+            workspace: workspace.to_path_buf(),
+            gitignore: builder.build()?,
+            patterns,
+        })
+    }
+
+    // Changes of junk files, build outputs and ignored files do not count
+    fn matches(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.workspace) else {
+            return false;
+        };
+
+        let generated = relative.components().any(|component| {
+            let name = component.as_os_str().to_string_lossy();
+            is_junk(&name) || BUILD_DIRS.contains(&name.as_ref())
+        });
+
+        if generated
+            || self
+                .gitignore
+                .matched_path_or_any_parents(relative, path.is_dir())
+                .is_ignore()
+        {
+            return false;
+        }
+
+        self.patterns.is_empty()
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches_path(relative))
+    }
+}